@@ -0,0 +1,511 @@
+// Things that steer a snake. Players steer through a TurnBuffer that the game fills from
+// the keyboard or a gamepad, the AI strategies look at the board and pick a turn themselves.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{Board, Cell, Direction, GameState, Snake, TurnBuffer, WallMode};
+
+pub trait SnakeController {
+    fn name(&self) -> &'static str;
+
+    // false while it's still making up its mind about the next step, which waits for it
+    fn ready(&mut self, _game: &GameState, _snake: usize) -> bool {
+        true
+    }
+
+    // the turn `snake` makes on the next step, None keeps it going the way it is
+    fn next_turn(&mut self, game: &GameState, snake: usize) -> Option<Direction>;
+}
+
+impl SnakeController for TurnBuffer {
+    fn name(&self) -> &'static str {
+        "Human"
+    }
+
+    fn next_turn(&mut self, game: &GameState, snake: usize) -> Option<Direction> {
+        self.pop(game.snakes()[snake].direction())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Strategy {
+    // the shortest way to the food, whatever happens after
+    Greedy,
+    // the shortest way to the food, as long as the snake can still reach its tail once it's eaten
+    #[default]
+    AStar,
+    // one loop over the whole board, so it never runs into itself
+    Hamiltonian,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 3] = [Strategy::Greedy, Strategy::AStar, Strategy::Hamiltonian];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Greedy => "Greedy",
+            Strategy::AStar => "AStar",
+            Strategy::Hamiltonian => "Hamiltonian",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Strategy> {
+        Strategy::ALL.into_iter().find(|strategy| strategy.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn controller(self) -> Box<dyn SnakeController + Send + Sync> {
+        match self {
+            Strategy::Greedy => Box::new(Greedy),
+            Strategy::AStar => Box::new(AStar),
+            Strategy::Hamiltonian => Box::<Hamiltonian>::default(),
+        }
+    }
+}
+
+// Who steers a snake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Pilot {
+    #[default]
+    Human,
+    Ai(Strategy),
+}
+
+impl Pilot {
+    pub fn name(self) -> &'static str {
+        match self {
+            Pilot::Human => "Human",
+            Pilot::Ai(strategy) => strategy.name(),
+        }
+    }
+
+    // None for a human, their turns come from the game's turn buffers
+    pub fn controller(self) -> Option<Box<dyn SnakeController + Send + Sync>> {
+        match self {
+            Pilot::Human => None,
+            Pilot::Ai(strategy) => Some(strategy.controller()),
+        }
+    }
+}
+
+// The board as one snake sees it. Each cell holds how many more steps it stays blocked
+// for, so a path can go through where the tail is now if it will have moved on by then.
+struct Grid {
+    board: Board,
+    wraps: bool,
+    blocked: Vec<u32>,
+}
+
+impl Grid {
+    fn new(game: &GameState, snake: &Snake) -> Self {
+        Grid::with_body(game, snake.body().iter().copied(), snake.pending_growth())
+    }
+
+    // `body` head first, `growth` is how many steps the tail stays put
+    fn with_body(game: &GameState, body: impl ExactSizeIterator<Item = Cell>, growth: u32) -> Self {
+        let board = game.board();
+        let mut grid = Grid {
+            board,
+            wraps: game.config().wall_mode == WallMode::Wrap,
+            blocked: vec![0; board.cell_count()],
+        };
+        let length = body.len() as u32;
+        for (index, cell) in body.enumerate() {
+            if board.contains(cell) {
+                let at = grid.index(cell);
+                grid.blocked[at] = grid.blocked[at].max(length - index as u32 + growth);
+            }
+        }
+        grid
+    }
+
+    fn index(&self, cell: Cell) -> usize {
+        (cell.y * self.board.width + cell.x) as usize
+    }
+
+    fn cell(&self, index: usize) -> Cell {
+        let index = index as i32;
+        Cell::new(index % self.board.width, index / self.board.width)
+    }
+
+    // where a step from `cell` ends up, None off the board. Clamped walls count as solid,
+    // pushing into one with a body behind the head is a bite.
+    fn neighbor(&self, cell: Cell, direction: Direction) -> Option<Cell> {
+        let next = cell.step(direction);
+        if self.wraps {
+            Some(self.board.wrap(next))
+        } else {
+            Some(next).filter(|next| self.board.contains(*next))
+        }
+    }
+
+    // can the head be in `cell` after `steps` moves
+    fn open(&self, cell: Cell, steps: u32) -> bool {
+        self.blocked[self.index(cell)] <= steps
+    }
+
+    fn direction_to(&self, from: Cell, to: Cell) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|direction| self.neighbor(from, *direction) == Some(to))
+    }
+
+    // steps between two cells with nothing in the way, going over the edge if it wraps
+    fn distance(&self, from: Cell, to: Cell) -> u32 {
+        let (dx, dy) = ((from.x - to.x).unsigned_abs(), (from.y - to.y).unsigned_abs());
+        if self.wraps {
+            dx.min(self.board.width as u32 - dx) + dy.min(self.board.height as u32 - dy)
+        } else {
+            dx + dy
+        }
+    }
+
+    // Shortest path from `from` to `to`, not counting `from`. The first step can't be `back`,
+    // the sim ignores a turn straight back. A* when `guided`, a plain breadth first search
+    // otherwise.
+    fn path(&self, from: Cell, back: Direction, to: Cell, guided: bool) -> Option<Vec<Cell>> {
+        let heuristic = |cell: Cell| if guided { self.distance(cell, to) } else { 0 };
+        let mut came_from: Vec<Option<usize>> = vec![None; self.blocked.len()];
+        let mut steps = vec![u32::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        steps[self.index(from)] = 0;
+        open.push(Reverse((heuristic(from), 0, self.index(from))));
+
+        while let Some(Reverse((_, at_steps, at))) = open.pop() {
+            if at_steps > steps[at] {
+                continue;
+            }
+            let cell = self.cell(at);
+            if cell == to && cell != from {
+                let mut path = vec![cell];
+                let mut at = at;
+                while let Some(previous) = came_from[at].filter(|previous| *previous != self.index(from)) {
+                    path.push(self.cell(previous));
+                    at = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for direction in Direction::ALL {
+                if cell == from && direction == back {
+                    continue;
+                }
+                let Some(next) = self.neighbor(cell, direction) else { continue };
+                let next_steps = at_steps + 1;
+                let index = self.index(next);
+                if !self.open(next, next_steps) || next_steps >= steps[index] {
+                    continue;
+                }
+                steps[index] = next_steps;
+                came_from[index] = Some(at);
+                open.push(Reverse((next_steps + heuristic(next), next_steps, index)));
+            }
+        }
+        None
+    }
+
+    // how many cells the head could get to starting from `from`
+    fn room(&self, from: Cell, steps: u32) -> usize {
+        let mut seen = vec![false; self.blocked.len()];
+        let mut queue = VecDeque::from([(from, steps)]);
+        seen[self.index(from)] = true;
+        let mut count = 0;
+        while let Some((cell, steps)) = queue.pop_front() {
+            count += 1;
+            for direction in Direction::ALL {
+                let Some(next) = self.neighbor(cell, direction) else { continue };
+                let index = self.index(next);
+                if !seen[index] && self.open(next, steps + 1) {
+                    seen[index] = true;
+                    queue.push_back((next, steps + 1));
+                }
+            }
+        }
+        count
+    }
+}
+
+// Nothing but the turn itself, going straight on is no turn at all
+fn turn(snake: &Snake, direction: Option<Direction>) -> Option<Direction> {
+    direction.filter(|direction| *direction != snake.direction())
+}
+
+// When there's no plan, at least go where there's the most room. Straight on wins a tie.
+fn safest_move(grid: &Grid, snake: &Snake) -> Option<Direction> {
+    let back = snake.direction().opposite();
+    Direction::ALL
+        .into_iter()
+        .filter(|direction| *direction != back)
+        .filter_map(|direction| Some((direction, grid.neighbor(snake.head(), direction).filter(|cell| grid.open(*cell, 1))?)))
+        .max_by_key(|(direction, cell)| (grid.room(*cell, 1), *direction == snake.direction()))
+        .map(|(direction, _)| direction)
+}
+
+// what the snake will look like after following `path`, and whether it can still get to
+// its tail from there. If it can it can't have boxed itself in.
+fn tail_reachable_after(game: &GameState, snake: &Snake, path: &[Cell]) -> bool {
+    let grown = snake.pending_growth().min(path.len() as u32);
+    let mut growth = snake.pending_growth() - grown;
+    if path.last().is_some_and(|cell| game.food() == Some(*cell)) {
+        growth += game.config().growth;
+    }
+    let length = snake.length() + grown as usize;
+    let body: Vec<Cell> = path.iter().rev().chain(snake.body().iter()).copied().take(length).collect();
+    if body.len() < 2 {
+        return true;
+    }
+    let grid = Grid::with_body(game, body.iter().copied(), growth);
+    let head = body[0];
+    let moved = grid.direction_to(body[1], head).unwrap_or(snake.direction());
+    grid.path(head, moved.opposite(), body[body.len() - 1], true).is_some()
+}
+
+pub struct Greedy;
+
+impl SnakeController for Greedy {
+    fn name(&self) -> &'static str {
+        Strategy::Greedy.name()
+    }
+
+    fn next_turn(&mut self, game: &GameState, snake: usize) -> Option<Direction> {
+        let snake = &game.snakes()[snake];
+        let grid = Grid::new(game, snake);
+        let back = snake.direction().opposite();
+        let direction = match game.food().and_then(|food| grid.path(snake.head(), back, food, false)) {
+            Some(path) => grid.direction_to(snake.head(), path[0]),
+            None => safest_move(&grid, snake),
+        };
+        turn(snake, direction)
+    }
+}
+
+#[derive(Default)]
+pub struct AStar;
+
+impl SnakeController for AStar {
+    fn name(&self) -> &'static str {
+        Strategy::AStar.name()
+    }
+
+    fn next_turn(&mut self, game: &GameState, snake: usize) -> Option<Direction> {
+        let snake = &game.snakes()[snake];
+        let grid = Grid::new(game, snake);
+        let (head, back) = (snake.head(), snake.direction().opposite());
+        let to_food = game
+            .food()
+            .and_then(|food| grid.path(head, back, food, true))
+            .filter(|path| tail_reachable_after(game, snake, path));
+        let direction = match to_food {
+            Some(path) => grid.direction_to(head, path[0]),
+            // With no safe way to the food, take any move that keeps the tail in reach and
+            // wander away from the food until a way opens up. Heading straight back to it
+            // would go round in the same circle forever.
+            None => Direction::ALL
+                .into_iter()
+                .filter(|direction| *direction != back)
+                .filter_map(|direction| Some((direction, grid.neighbor(head, direction)?)))
+                .filter(|(_, cell)| grid.open(*cell, 1) && tail_reachable_after(game, snake, &[*cell]))
+                .max_by_key(|(direction, cell)| {
+                    (game.food().map_or(0, |food| grid.distance(*cell, food)), *direction == snake.direction())
+                })
+                .map(|(direction, _)| direction)
+                .or_else(|| safest_move(&grid, snake)),
+        };
+        turn(snake, direction)
+    }
+}
+
+// A loop through every cell of the board. There's always one if a side is an even
+// number of cells long.
+struct Cycle {
+    board: Board,
+    order: Vec<Cell>,
+    // where each cell comes in `order`
+    position: Vec<usize>,
+}
+
+impl Cycle {
+    fn new(board: Board) -> Option<Self> {
+        let (columns, rows, flipped) = match (board.width % 2, board.height % 2) {
+            (0, _) => (board.width, board.height, false),
+            (_, 0) => (board.height, board.width, true),
+            _ => return None,
+        };
+        if columns < 2 || rows < 2 {
+            return None;
+        }
+        // up the first column, back and forth through the rest above the bottom row, then
+        // home along the bottom row
+        let mut order: Vec<(i32, i32)> = (0..rows).map(|y| (0, y)).collect();
+        for x in 1..columns {
+            if x % 2 == 1 {
+                order.extend((1..rows).rev().map(|y| (x, y)));
+            } else {
+                order.extend((1..rows).map(|y| (x, y)));
+            }
+        }
+        order.extend((1..columns).rev().map(|x| (x, 0)));
+
+        let order: Vec<Cell> =
+            order.into_iter().map(|(x, y)| if flipped { Cell::new(y, x) } else { Cell::new(x, y) }).collect();
+        let mut position = vec![0; order.len()];
+        for (index, cell) in order.iter().enumerate() {
+            position[(cell.y * board.width + cell.x) as usize] = index;
+        }
+        Some(Cycle { board, order, position })
+    }
+
+    fn position(&self, cell: Cell) -> usize {
+        self.position[(cell.y * self.board.width + cell.x) as usize]
+    }
+
+    // how far along the loop `to` is from `from`
+    fn ahead(&self, from: Cell, to: Cell) -> usize {
+        let length = self.order.len();
+        (self.position(to) + length - self.position(from)) % length
+    }
+}
+
+// Follows a loop over the whole board, so the body is always behind the head and out of
+// its way. That can't lose, but it's slow, so while the snake is short it cuts across to
+// cells further along the loop as long as it stays clear of its tail.
+#[derive(Default)]
+pub struct Hamiltonian {
+    cycle: Option<Cycle>,
+    // for boards with no loop, both sides odd
+    fallback: AStar,
+}
+
+impl SnakeController for Hamiltonian {
+    fn name(&self) -> &'static str {
+        Strategy::Hamiltonian.name()
+    }
+
+    fn next_turn(&mut self, game: &GameState, index: usize) -> Option<Direction> {
+        if self.cycle.as_ref().map(|cycle| cycle.board) != Some(game.board()) {
+            self.cycle = Cycle::new(game.board());
+        }
+        let Some(cycle) = &self.cycle else { return self.fallback.next_turn(game, index) };
+
+        let snake = &game.snakes()[index];
+        let grid = Grid::new(game, snake);
+        let (head, back) = (snake.head(), snake.direction().opposite());
+        let cells = cycle.order.len();
+        let mut target = cycle.order[(cycle.position(head) + 1) % cells];
+
+        if let Some(food) = game.food().filter(|_| snake.length() * 2 < cells) {
+            let tail_gap = if snake.length() > 1 { cycle.ahead(head, *snake.body().back()?) } else { cells };
+            // room for everything the snake could still grow by
+            let margin = (snake.pending_growth() + game.config().growth) as usize + 1;
+            for direction in Direction::ALL.into_iter().filter(|direction| *direction != back) {
+                let Some(cell) = grid.neighbor(head, direction).filter(|cell| grid.open(*cell, 1)) else { continue };
+                let ahead = cycle.ahead(head, cell);
+                if ahead > cycle.ahead(head, target) && ahead <= cycle.ahead(head, food) && ahead + margin < tail_gap {
+                    target = cell;
+                }
+            }
+        }
+
+        match grid.direction_to(head, target) {
+            Some(direction) if direction != back && grid.open(target, 1) => turn(snake, Some(direction)),
+            // off the loop, which only happens right after a respawn
+            _ => self.fallback.next_turn(game, index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use Direction::{Down, Left, Right, Up};
+    use crate::sim::{GameConfig, GameEvent};
+
+    fn game(width: i32, height: i32, seed: u64) -> GameState {
+        let config = GameConfig { board: Board::new(width, height), wall_mode: WallMode::Solid, ..GameConfig::default() };
+        GameState::new(config, seed)
+    }
+
+    #[test]
+    fn cycle_visits_every_cell_once() {
+        for (width, height) in [(2, 2), (6, 6), (5, 4), (4, 7), (10, 3)] {
+            let board = Board::new(width, height);
+            let cycle = Cycle::new(board).unwrap();
+            assert_eq!(cycle.order.len(), board.cell_count());
+            assert_eq!(cycle.order.iter().collect::<HashSet<_>>().len(), board.cell_count());
+            for (index, cell) in cycle.order.iter().enumerate() {
+                assert!(board.contains(*cell));
+                assert_eq!(cycle.position(*cell), index);
+                // and every step of the loop, the last one back to the start too, is a single move
+                let next = cycle.order[(index + 1) % cycle.order.len()];
+                assert_eq!((cell.x - next.x).abs() + (cell.y - next.y).abs(), 1, "{}x{} at {:?}", width, height, cell);
+            }
+        }
+        assert!(Cycle::new(Board::new(5, 7)).is_none());
+    }
+
+    #[test]
+    fn hamiltonian_fills_the_board() {
+        let mut game = game(6, 6, 3);
+        let mut pilot = Hamiltonian::default();
+        for _ in 0..100_000 {
+            let turn = pilot.next_turn(&game, 0);
+            let events = game.step(&[turn]);
+            assert_eq!(game.snakes()[0].death(), None, "died at length {}", game.snakes()[0].length());
+            if events.contains(&GameEvent::BoardFull) {
+                assert_eq!(game.snakes()[0].length(), 36);
+                return;
+            }
+        }
+        panic!("never filled the board, got to length {}", game.snakes()[0].length());
+    }
+
+    // one step for each turn, growing by `growth` on the way. Park the food out of the way first.
+    fn steer(game: &mut GameState, growth: u32, turns: &[Direction]) {
+        for _ in 0..growth {
+            game.grow(0);
+        }
+        for turn in turns {
+            game.step(&[Some(*turn)]);
+        }
+        assert!(game.snakes()[0].is_alive());
+    }
+
+    #[test]
+    fn greedy_takes_the_only_safe_move() {
+        let mut game = game(9, 9, 1);
+        assert!(game.place_food(Cell::new(0, 0)));
+        // up into the top right corner, three long
+        steer(&mut game, 2, &[Right, Right, Right, Right, Up, Up, Up, Up]);
+        assert_eq!(game.snakes()[0].head(), Cell::new(8, 8));
+        assert_eq!(game.snakes()[0].length(), 3);
+
+        assert_eq!(Greedy.next_turn(&game, 0), Some(Left));
+        game.step(&[Some(Left)]);
+        assert!(game.snakes()[0].is_alive());
+    }
+
+    #[test]
+    fn astar_wont_eat_where_it_cant_get_out() {
+        let mut game = game(5, 5, 1);
+        assert!(game.place_food(Cell::new(4, 0)));
+        // round to the bottom, then ten long with the head at the mouth of a pocket it walls in
+        steer(&mut game, 0, &[Left, Down, Down, Right]);
+        steer(&mut game, 9, &[Up, Up, Up, Up, Left, Down, Down, Down, Left]);
+        let body: Vec<(i32, i32)> = game.snakes()[0].body().iter().map(|cell| (cell.x, cell.y)).collect();
+        assert_eq!(body, [(0, 1), (1, 1), (1, 2), (1, 3), (1, 4), (2, 4), (2, 3), (2, 2), (2, 1), (2, 0)]);
+
+        // the food at the far end of the pocket, which has no way back out
+        assert!(game.place_food(Cell::new(0, 4)));
+        assert_eq!(AStar.next_turn(&game, 0), Some(Down));
+
+        // greedy goes in after it and is stuck there
+        let mut greedy = game.clone();
+        assert_eq!(Greedy.next_turn(&greedy, 0), Some(Up));
+        for _ in 0..5 {
+            let turn = Greedy.next_turn(&greedy, 0);
+            greedy.step(&[turn]);
+        }
+        assert!(!greedy.snakes()[0].is_alive());
+    }
+}
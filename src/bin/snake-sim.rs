@@ -0,0 +1,396 @@
+// Plays lots of games with an AI pilot and no window, for comparing strategies and rule
+// changes. Only the rules in snake_game::sim run, there's no bevy in here at all.
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use snake_game::ai::Strategy;
+use snake_game::sim::{Board, DeathCause, GameConfig, GameEvent, GameState, WallMode};
+
+const USAGE: &str = "\
+Usage: snake-sim [options]
+  --games <n>        how many games to play (100)
+  --seed <n>         seed of the first game, the rest count up from it (0)
+  --ai <strategy>    greedy, astar or hamiltonian (astar)
+  --board <WxH>      board size in cells, 5 to 256 a side (64x48)
+  --mode <mode>      solid, wrap or clamp (solid)
+  --lives <n>        lives per game (1)
+  --growth <n>       segments added per piece of food (1)
+  --max-ticks <n>    give up on a game after this many moves (100000)
+  --threads <n>      games played at once (one per core)
+  --format <format>  csv or json (csv)
+  --output <file>    write the report here instead of to stdout";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    games: u64,
+    seed: u64,
+    strategy: Strategy,
+    config: GameConfig,
+    max_ticks: u64,
+    threads: usize,
+    format: Format,
+    output: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            games: 100,
+            seed: 0,
+            strategy: Strategy::AStar,
+            config: GameConfig::default(),
+            max_ticks: 100_000,
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            format: Format::Csv,
+            output: None,
+        }
+    }
+}
+
+impl Options {
+    // anything that doesn't make sense stops the run, a report with the wrong settings is
+    // worse than none
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            // both "--flag value" and "--flag=value" work
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            if flag == "--help" || flag == "-h" {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            let value = inline_value.or_else(|| args.next()).ok_or(format!("{} needs a value", flag))?;
+            let number = || value.parse::<u64>().map_err(|_| format!("{} needs a whole number, not {}", flag, value));
+
+            match flag.as_str() {
+                "--games" => options.games = number()?,
+                "--seed" => options.seed = number()?,
+                "--ai" => {
+                    options.strategy =
+                        Strategy::parse(&value).ok_or(format!("no AI called {}, use greedy, astar or hamiltonian", value))?
+                }
+                "--board" => options.config.board = parse_board(&value)?,
+                "--mode" => {
+                    options.config.wall_mode = WallMode::ALL
+                        .into_iter()
+                        .find(|mode| mode.name().eq_ignore_ascii_case(&value))
+                        .ok_or(format!("no wall mode called {}, use solid, wrap or clamp", value))?
+                }
+                "--lives" => options.config.lives = number()?.max(1) as u32,
+                "--growth" => options.config.growth = number()? as u32,
+                "--max-ticks" => options.max_ticks = number()?,
+                "--threads" => options.threads = number()?.max(1) as usize,
+                "--format" => {
+                    options.format = match value.to_lowercase().as_str() {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        _ => return Err(format!("no format called {}, use csv or json", value)),
+                    }
+                }
+                "--output" => options.output = Some(PathBuf::from(value)),
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// the same sizes the game itself will play on
+fn parse_board(text: &str) -> Result<Board, String> {
+    let size = text.split_once('x').and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+    let sides = Board::MIN_SIDE..=Board::MAX_SIDE;
+    match size {
+        Some((width, height)) if sides.contains(&width) && sides.contains(&height) => Ok(Board::new(width, height)),
+        Some((width, height)) => Err(format!(
+            "a {}x{} board won't work, each side needs {} to {} cells",
+            width,
+            height,
+            Board::MIN_SIDE,
+            Board::MAX_SIDE
+        )),
+        None => Err(format!("{} isn't a board size, use WIDTHxHEIGHT", text)),
+    }
+}
+
+// how a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+enum Ending {
+    Wall,
+    OwnBody,
+    BoardFull,
+    // still going at --max-ticks, usually an AI going round in circles
+    OutOfTime,
+}
+
+impl From<DeathCause> for Ending {
+    fn from(cause: DeathCause) -> Self {
+        match cause {
+            DeathCause::Wall => Ending::Wall,
+            DeathCause::OwnBody => Ending::OwnBody,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct GameReport {
+    seed: u64,
+    score: u32,
+    length: usize,
+    ticks: u64,
+    ending: Ending,
+    runtime_ms: f64,
+}
+
+// Plays one game to the end the same way the game does, dead snakes come back until
+// they're out of lives
+fn play(options: &Options, seed: u64) -> GameReport {
+    let started = Instant::now();
+    let mut game = GameState::new(options.config, seed);
+    let mut pilot = options.strategy.controller();
+    let ending = loop {
+        let snake = &game.snakes()[0];
+        if let Some(cause) = snake.death() {
+            if game.respawn(0).is_none() {
+                break Ending::from(cause);
+            }
+        }
+        if game.tick() >= options.max_ticks {
+            break Ending::OutOfTime;
+        }
+        let turn = pilot.next_turn(&game, 0);
+        if game.step(&[turn]).contains(&GameEvent::BoardFull) {
+            break Ending::BoardFull;
+        }
+    };
+    GameReport {
+        seed,
+        score: game.score(),
+        length: game.snakes()[0].length(),
+        ticks: game.tick(),
+        ending,
+        runtime_ms: started.elapsed().as_secs_f64() * 1000.,
+    }
+}
+
+// every game, spread over the threads, in seed order
+fn play_all(options: &Options) -> Vec<GameReport> {
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(Vec::with_capacity(options.games as usize));
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.min(options.games.max(1) as usize) {
+            scope.spawn(|| loop {
+                let game = next.fetch_add(1, Ordering::Relaxed) as u64;
+                if game >= options.games {
+                    break;
+                }
+                let report = play(options, options.seed + game);
+                reports.lock().unwrap().push(report);
+            });
+        }
+    });
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|report| report.seed);
+    reports
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct Summary {
+    games: usize,
+    mean_score: f64,
+    median_score: f64,
+    min_score: u32,
+    max_score: u32,
+    mean_length: f64,
+    mean_ticks: f64,
+    walls: usize,
+    own_body: usize,
+    boards_filled: usize,
+    out_of_time: usize,
+    mean_runtime_ms: f64,
+    total_runtime_ms: f64,
+}
+
+impl Summary {
+    fn new(reports: &[GameReport], total_runtime: Duration) -> Self {
+        if reports.is_empty() {
+            return Summary::default();
+        }
+        let games = reports.len();
+        let mean = |value: &dyn Fn(&GameReport) -> f64| reports.iter().map(value).sum::<f64>() / games as f64;
+        let count = |ending: Ending| reports.iter().filter(|report| report.ending == ending).count();
+        let mut scores: Vec<u32> = reports.iter().map(|report| report.score).collect();
+        scores.sort_unstable();
+        // the same middle score twice when there's an odd number of games
+        let median_score = (scores[(games - 1) / 2] + scores[games / 2]) as f64 / 2.;
+        Summary {
+            games,
+            mean_score: mean(&|report| report.score as f64),
+            median_score,
+            min_score: scores[0],
+            max_score: scores[games - 1],
+            mean_length: mean(&|report| report.length as f64),
+            mean_ticks: mean(&|report| report.ticks as f64),
+            walls: count(Ending::Wall),
+            own_body: count(Ending::OwnBody),
+            boards_filled: count(Ending::BoardFull),
+            out_of_time: count(Ending::OutOfTime),
+            mean_runtime_ms: mean(&|report| report.runtime_ms),
+            total_runtime_ms: total_runtime.as_secs_f64() * 1000.,
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Games: {}", self.games)?;
+        writeln!(
+            f,
+            "Score: mean {:.1}, median {:.1}, min {}, max {}",
+            self.mean_score, self.median_score, self.min_score, self.max_score
+        )?;
+        writeln!(f, "Length: mean {:.1}, ticks: mean {:.1}", self.mean_length, self.mean_ticks)?;
+        writeln!(
+            f,
+            "Endings: {} wall, {} own body, {} board full, {} out of time",
+            self.walls, self.own_body, self.boards_filled, self.out_of_time
+        )?;
+        write!(
+            f,
+            "Runtime: {:.1}ms per game, {:.1}s in all",
+            self.mean_runtime_ms,
+            self.total_runtime_ms / 1000.
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    ai: &'static str,
+    board: String,
+    wall_mode: &'static str,
+    lives: u32,
+    growth: u32,
+    games: &'a [GameReport],
+    summary: &'a Summary,
+}
+
+fn write_report(options: &Options, reports: &[GameReport], summary: &Summary, out: &mut dyn Write) -> io::Result<()> {
+    match options.format {
+        Format::Csv => {
+            writeln!(out, "seed,score,length,ticks,ending,runtime_ms")?;
+            for report in reports {
+                writeln!(
+                    out,
+                    "{},{},{},{},{:?},{:.3}",
+                    report.seed, report.score, report.length, report.ticks, report.ending, report.runtime_ms
+                )?;
+            }
+        }
+        Format::Json => {
+            let config = &options.config;
+            let report = JsonReport {
+                ai: options.strategy.name(),
+                board: format!("{}x{}", config.board.width, config.board.height),
+                wall_mode: config.wall_mode.name(),
+                lives: config.lives,
+                growth: config.growth,
+                games: reports,
+                summary,
+            };
+            serde_json::to_writer_pretty(&mut *out, &report)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let config = &options.config;
+    eprintln!(
+        "Playing {} games of {} on {}x{} {}, {} at a time",
+        options.games,
+        options.strategy.name(),
+        config.board.width,
+        config.board.height,
+        config.wall_mode.name(),
+        options.threads
+    );
+    let started = Instant::now();
+    let reports = play_all(&options);
+    let summary = Summary::new(&reports, started.elapsed());
+
+    let written = match &options.output {
+        Some(path) => File::create(path).and_then(|mut file| write_report(&options, &reports, &summary, &mut file)),
+        None => write_report(&options, &reports, &summary, &mut io::stdout().lock()),
+    };
+    if let Err(err) = written {
+        eprintln!("Can't write the report: {}", err);
+        std::process::exit(1);
+    }
+    // the summary goes next to the report rather than in it, so a CSV stays one table
+    eprintln!("{}", summary);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn reads_flags_both_ways() {
+        let options = parse(&["--games", "5", "--seed=7", "--ai", "Hamiltonian", "--mode=wrap", "--format", "json"]).unwrap();
+        assert_eq!(options.games, 5);
+        assert_eq!(options.seed, 7);
+        assert_eq!(options.strategy, Strategy::Hamiltonian);
+        assert_eq!(options.config.wall_mode, WallMode::Wrap);
+        assert_eq!(options.format, Format::Json);
+
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.games, 100);
+        assert_eq!(options.config, GameConfig::default());
+    }
+
+    #[test]
+    fn board_sizes_the_game_can_play() {
+        assert_eq!(parse(&["--board", "5x5"]).unwrap().config.board, Board::new(5, 5));
+        assert_eq!(parse(&["--board=256x12"]).unwrap().config.board, Board::new(256, 12));
+        for size in ["4x10", "10x257", "0x0", "-8x8", "10", "10x", "bigxbig", "small"] {
+            assert!(parse(&["--board", size]).is_err(), "{}", size);
+        }
+    }
+
+    #[test]
+    fn bad_arguments_stop_the_run() {
+        assert!(parse(&["--games"]).is_err());
+        assert!(parse(&["--games", "lots"]).is_err());
+        assert!(parse(&["--ai", "random"]).is_err());
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--colour", "red"]).is_err());
+    }
+}
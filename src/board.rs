@@ -0,0 +1,275 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use snake_game::sim::{Board, Cell, Direction};
+
+use crate::snake::MoveTime;
+
+// Size of the play area, everything in the sim is in cells and only gets turned into
+// pixels here
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoardConfig {
+    pub width_cells: i32,
+    pub height_cells: i32,
+    pub cell_px: f32,
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        BoardConfig {
+            width_cells: 64,
+            height_cells: 48,
+            cell_px: 10.,
+        }
+    }
+}
+
+// the board sizes the settings menu steps through, in cells
+const PRESETS: [(&str, i32, i32); 4] = [("Small", 32, 24), ("Medium", 64, 48), ("Large", 96, 72), ("Huge", 128, 96)];
+
+const MIN_CELL_PX: f32 = 2.;
+const MAX_CELL_PX: f32 = 64.;
+// biggest the board can be before zooming, so the starting window fits on a screen
+const MAX_BOARD_PX: f32 = 4096.;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoardError {
+    Unknown(String),
+    Cells(i32, i32),
+    CellSize(f32),
+    TooBig(f32, f32),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardError::Unknown(text) => {
+                write!(f, "{} isn't a board size, use small, medium, large, huge or WIDTHxHEIGHT[@CELL_PX]", text)
+            }
+            BoardError::Cells(width, height) => write!(
+                f,
+                "a {}x{} board won't work, each side needs {} to {} cells",
+                width, height, Board::MIN_SIDE, Board::MAX_SIDE
+            ),
+            BoardError::CellSize(px) => write!(f, "cells of {}px won't work, use {} to {}", px, MIN_CELL_PX, MAX_CELL_PX),
+            BoardError::TooBig(width, height) => {
+                write!(f, "a {}x{}px board is too big, keep it under {}px a side", width, height, MAX_BOARD_PX)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+impl BoardConfig {
+    // Reads a preset name or WIDTHxHEIGHT, with @CELL_PX on the end to change the cell size
+    // too. Anything not given is kept from `base`.
+    pub fn parse(text: &str, base: BoardConfig) -> Result<BoardConfig, BoardError> {
+        let unknown = || BoardError::Unknown(text.to_string());
+        let (size, cell_px) = match text.split_once('@') {
+            Some((size, px)) => (size, Some(px.trim().parse::<f32>().map_err(|_| unknown())?)),
+            None => (text, None),
+        };
+        let mut config = match PRESETS.iter().find(|(name, _, _)| name.eq_ignore_ascii_case(size.trim())) {
+            Some((_, width, height)) => base.with_cells(*width, *height),
+            None => {
+                let (width, height) = size.split_once(['x', 'X']).ok_or_else(unknown)?;
+                let width = width.trim().parse().map_err(|_| unknown())?;
+                let height = height.trim().parse().map_err(|_| unknown())?;
+                base.with_cells(width, height)
+            }
+        };
+        if let Some(cell_px) = cell_px {
+            config.cell_px = cell_px;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), BoardError> {
+        let cells = Board::MIN_SIDE..=Board::MAX_SIDE;
+        if !cells.contains(&self.width_cells) || !cells.contains(&self.height_cells) {
+            return Err(BoardError::Cells(self.width_cells, self.height_cells));
+        }
+        if !(MIN_CELL_PX..=MAX_CELL_PX).contains(&self.cell_px) {
+            return Err(BoardError::CellSize(self.cell_px));
+        }
+        if self.width_px() > MAX_BOARD_PX || self.height_px() > MAX_BOARD_PX {
+            return Err(BoardError::TooBig(self.width_px(), self.height_px()));
+        }
+        Ok(())
+    }
+
+    fn with_cells(self, width_cells: i32, height_cells: i32) -> BoardConfig {
+        BoardConfig {
+            width_cells,
+            height_cells,
+            ..self
+        }
+    }
+
+    // "Medium" and so on, None for a size that isn't one of the presets
+    pub fn preset_name(&self) -> Option<&'static str> {
+        PRESETS
+            .iter()
+            .find(|(_, width, height)| *width == self.width_cells && *height == self.height_cells)
+            .map(|(name, _, _)| *name)
+    }
+
+    // the next preset up, a custom size goes back to the first one
+    pub fn next_preset(&self) -> BoardConfig {
+        let index = PRESETS
+            .iter()
+            .position(|(_, width, height)| *width == self.width_cells && *height == self.height_cells)
+            .map_or(0, |index| (index + 1) % PRESETS.len());
+        let (_, width, height) = PRESETS[index];
+        self.with_cells(width, height)
+    }
+
+    pub fn board(&self) -> Board {
+        Board::new(self.width_cells, self.height_cells)
+    }
+
+    pub fn width_px(&self) -> f32 {
+        self.width_cells as f32 * self.cell_px
+    }
+
+    pub fn height_px(&self) -> f32 {
+        self.height_cells as f32 * self.cell_px
+    }
+
+    // world position of the middle of a cell, the board is centered on the camera
+    pub fn cell_to_world(&self, x: i32, y: i32) -> Vec2 {
+        Vec2::new(
+            (x as f32 - self.width_cells as f32 / 2. + 0.5) * self.cell_px,
+            (y as f32 - self.height_cells as f32 / 2. + 0.5) * self.cell_px,
+        )
+    }
+
+    // the cell a world position falls in, which may be off the board
+    pub fn world_to_cell(&self, position: Vec2) -> Cell {
+        Cell::new(
+            (position.x / self.cell_px + self.width_cells as f32 / 2.).floor() as i32,
+            (position.y / self.cell_px + self.height_cells as f32 / 2.).floor() as i32,
+        )
+    }
+}
+
+// Where an entity sits on the board. Transform is worked out from this in grid_to_transform
+// and should never be used for game logic.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GridPos {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl From<Cell> for GridPos {
+    fn from(cell: Cell) -> Self {
+        GridPos { x: cell.x, y: cell.y }
+    }
+}
+
+impl From<GridPos> for Cell {
+    fn from(pos: GridPos) -> Self {
+        Cell::new(pos.x, pos.y)
+    }
+}
+
+pub fn grid_to_transform(
+    board_config: Res<BoardConfig>,
+    mut query: Query<(Ref<GridPos>, &mut Transform), Without<Motion>>,
+) {
+    for (grid_pos, mut transform) in &mut query {
+        if !board_config.is_changed() && !grid_pos.is_changed() {
+            continue;
+        }
+        let position = board_config.cell_to_world(grid_pos.x, grid_pos.y);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+// Entities with this slide from `from` to their GridPos over the course of a move instead
+// of jumping. Only the Transform moves, GridPos is still what the game goes by.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Motion {
+    pub from: GridPos,
+    // the sim tick `from` was recorded on, so it's only updated once per move
+    pub tick: u64,
+    // the segment turned in `from`, it stretches to keep the corner filled while it goes round
+    pub corner: bool,
+}
+
+impl Motion {
+    pub fn at(pos: GridPos, tick: u64) -> Self {
+        Motion { from: pos, tick, corner: false }
+    }
+}
+
+// Which way one step takes you from `from` to `to`, and whether it went across the wrap seam.
+// None if they aren't next to each other, like after a respawn.
+pub fn step_between(board: Board, from: Cell, to: Cell) -> Option<(Direction, bool)> {
+    Direction::ALL.into_iter().find_map(|direction| {
+        let next = from.step(direction);
+        if next == to {
+            Some((direction, false))
+        } else if board.wrap(next) == to {
+            Some((direction, true))
+        } else {
+            None
+        }
+    })
+}
+
+// how far through the current move we are, counting the time the fixed timestep hasn't used yet
+fn move_fraction(move_time: &MoveTime, fixed_time: &Time<Fixed>) -> f32 {
+    let duration = move_time.timer.duration().as_secs_f32();
+    if duration <= 0. {
+        return 1.;
+    }
+    ((move_time.timer.elapsed() + fixed_time.overstep()).as_secs_f32() / duration).min(1.)
+}
+
+pub fn interpolate_motion(
+    board_config: Res<BoardConfig>,
+    move_time: Res<MoveTime>,
+    fixed_time: Res<Time<Fixed>>,
+    mut query: Query<(&GridPos, &Motion, &mut Transform)>,
+) {
+    let fraction = move_fraction(&move_time, &fixed_time);
+    let board = board_config.board();
+    for (grid_pos, motion, mut transform) in &mut query {
+        let to = board_config.cell_to_world(grid_pos.x, grid_pos.y);
+        let from = board_config.cell_to_world(motion.from.x, motion.from.y);
+        let mut scale = Vec2::ONE;
+        let position = match step_between(board, motion.from.into(), (*grid_pos).into()) {
+            // out one side for the first half of the move, in the other for the second
+            Some((direction, true)) => {
+                let (x, y) = direction.offset();
+                let step = Vec2::new(x as f32, y as f32) * board_config.cell_px;
+                if fraction < 0.5 { from + step * fraction } else { to - step * (1. - fraction) }
+            }
+            Some((direction, false)) => {
+                let position = from.lerp(to, fraction);
+                if motion.corner {
+                    // cover everything from the corner cell to where the segment has got to
+                    let stretch = Vec2::ONE + (position - from).abs() / board_config.cell_px;
+                    scale = match direction {
+                        Direction::Left | Direction::Right => Vec2::new(stretch.x, 1.),
+                        Direction::Up | Direction::Down => Vec2::new(1., stretch.y),
+                    };
+                    (from + position) / 2.
+                } else {
+                    position
+                }
+            }
+            None => to,
+        };
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        transform.scale = scale.extend(1.);
+    }
+}
@@ -0,0 +1,249 @@
+// Lets a program written in anything steer a snake. Before each step the board goes out on
+// stdout as one line of JSON, and the bot answers with a direction on stdin. Nothing else
+// is written to stdout, the game logs to stderr.
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::SnakeController;
+use crate::sim::{Cell, Direction, GameState, WallMode};
+
+pub const DEFAULT_BOT_TIMEOUT: Duration = Duration::from_millis(100);
+
+// what the bot is sent each step. Cells are [x, y] with [0, 0] the bottom left corner.
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardState {
+    pub tick: u64,
+    pub score: u32,
+    pub width: i32,
+    pub height: i32,
+    pub wall_mode: WallMode,
+    // which of the snakes the bot is steering
+    pub you: usize,
+    pub food: Option<(i32, i32)>,
+    pub obstacles: Vec<(i32, i32)>,
+    pub snakes: Vec<SnakeState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnakeState {
+    // head first
+    pub body: Vec<(i32, i32)>,
+    pub direction: Direction,
+    pub alive: bool,
+    pub lives: u32,
+}
+
+fn xy(cell: Cell) -> (i32, i32) {
+    (cell.x, cell.y)
+}
+
+impl BoardState {
+    pub fn new(game: &GameState, you: usize) -> Self {
+        let mut obstacles: Vec<(i32, i32)> = game.obstacles().iter().copied().map(xy).collect();
+        // a HashSet comes out in any order, keep the lines the same from run to run
+        obstacles.sort_unstable();
+        BoardState {
+            tick: game.tick(),
+            score: game.score(),
+            width: game.board().width,
+            height: game.board().height,
+            wall_mode: game.config().wall_mode,
+            you,
+            food: game.food().map(xy),
+            obstacles,
+            snakes: game
+                .snakes()
+                .iter()
+                .map(|snake| SnakeState {
+                    body: snake.body().iter().copied().map(xy).collect(),
+                    direction: snake.direction(),
+                    alive: snake.is_alive(),
+                    lives: snake.lives(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// A reply can be a bare direction, "Left" or left, or {"direction": "Left", "tick": 12}.
+// With a tick, replies meant for an earlier step are told apart and skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+struct Reply {
+    direction: Direction,
+    tick: Option<u64>,
+}
+
+fn parse_reply(line: &str) -> Option<Reply> {
+    let line = line.trim();
+    if line.starts_with('{') {
+        return serde_json::from_str(line).ok();
+    }
+    let word = line.trim_matches('"');
+    let direction = Direction::ALL.into_iter().find(|direction| format!("{:?}", direction).eq_ignore_ascii_case(word))?;
+    Some(Reply { direction, tick: None })
+}
+
+// Stdin is read on a thread of its own and checked each frame without waiting on it, so
+// the window keeps drawing while the bot thinks. Clones share the one reader, there's only
+// the one stdin.
+#[derive(Clone)]
+pub struct StdioBot {
+    lines: Arc<Mutex<Receiver<String>>>,
+    timeout: Duration,
+    // the step the bot was last sent, and when
+    asked: Option<(u64, Instant)>,
+    // what it said about that step, Some(None) once it's too late or made no sense
+    answer: Option<Option<Direction>>,
+}
+
+impl StdioBot {
+    pub fn spawn(timeout: Duration) -> Self {
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        StdioBot {
+            lines: Arc::new(Mutex::new(lines)),
+            timeout,
+            asked: None,
+            answer: None,
+        }
+    }
+
+    // Sends the board once per step. Anything still waiting from before came too late for
+    // its own step and is dropped first.
+    fn ask(&mut self, game: &GameState, snake: usize) {
+        if self.asked.is_some_and(|(tick, _)| tick == game.tick()) {
+            return;
+        }
+        self.asked = Some((game.tick(), Instant::now()));
+        self.answer = None;
+        if let Ok(lines) = self.lines.lock() {
+            while lines.try_recv().is_ok() {}
+        }
+        let sent = serde_json::to_string(&BoardState::new(game, snake))
+            .map_err(io::Error::from)
+            .and_then(|line| {
+                let mut stdout = io::stdout().lock();
+                writeln!(stdout, "{}", line)?;
+                stdout.flush()
+            });
+        if sent.is_err() {
+            self.answer = Some(None);
+        }
+    }
+
+    // picks up the answer to the last question if it's in, or gives up once the time is up
+    fn poll(&mut self) {
+        let Some((tick, asked_at)) = self.asked else { return };
+        if self.answer.is_some() {
+            return;
+        }
+        let Ok(lines) = self.lines.lock() else {
+            self.answer = Some(None);
+            return;
+        };
+        loop {
+            let line = match lines.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => break,
+                // the bot is gone and stdin is closed
+                Err(TryRecvError::Disconnected) => {
+                    self.answer = Some(None);
+                    return;
+                }
+            };
+            match parse_reply(&line) {
+                Some(reply) if reply.tick.unwrap_or(tick) == tick => {
+                    self.answer = Some(Some(reply.direction));
+                    return;
+                }
+                Some(_) => continue,
+                None => {
+                    eprintln!("Bot: can't read {:?}, going straight on", line);
+                    self.answer = Some(None);
+                    return;
+                }
+            }
+        }
+        if asked_at.elapsed() >= self.timeout {
+            self.answer = Some(None);
+        }
+    }
+}
+
+impl SnakeController for StdioBot {
+    fn name(&self) -> &'static str {
+        "Bot"
+    }
+
+    fn ready(&mut self, game: &GameState, snake: usize) -> bool {
+        self.ask(game, snake);
+        self.poll();
+        self.answer.is_some()
+    }
+
+    fn next_turn(&mut self, game: &GameState, snake: usize) -> Option<Direction> {
+        self.ask(game, snake);
+        self.poll();
+        let direction = self.answer.take().flatten()?;
+        // the way it's already going isn't a turn
+        Some(direction).filter(|direction| *direction != game.snakes()[snake].direction())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Board, GameConfig};
+
+    #[test]
+    fn reads_bare_and_json_replies() {
+        let reply = |direction, tick| Some(Reply { direction, tick });
+        assert_eq!(parse_reply("Left"), reply(Direction::Left, None));
+        assert_eq!(parse_reply("  up\n"), reply(Direction::Up, None));
+        assert_eq!(parse_reply("\"DOWN\""), reply(Direction::Down, None));
+        assert_eq!(parse_reply(r#"{"direction": "Right", "tick": 12}"#), reply(Direction::Right, Some(12)));
+        assert_eq!(parse_reply(r#"{"direction":"Up"}"#), reply(Direction::Up, None));
+    }
+
+    #[test]
+    fn nonsense_replies_are_none() {
+        for line in ["", "sideways", "Left Right", "{", r#"{"direction": "left"}"#, r#"{"tick": 3}"#, "[\"Up\"]"] {
+            assert_eq!(parse_reply(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn board_state_round_trip() {
+        let config = GameConfig { board: Board::new(9, 7), lives: 2, ..GameConfig::default() };
+        let mut game = GameState::new(config, 1);
+        assert!(game.place_food(Cell::new(0, 0)));
+        game.grow(0);
+        game.step(&[Some(Direction::Right)]);
+        assert!(game.place_food(Cell::new(2, 5)));
+        game.add_obstacle(Cell::new(8, 1));
+        game.add_obstacle(Cell::new(3, 6));
+
+        let line = serde_json::to_string(&BoardState::new(&game, 0)).unwrap();
+        assert_eq!(
+            line,
+            r#"{"tick":1,"score":0,"width":9,"height":7,"wall_mode":"Solid","you":0,"food":[2,5],"obstacles":[[3,6],[8,1]],"snakes":[{"body":[[5,3],[4,3]],"direction":"Right","alive":true,"lives":2}]}"#
+        );
+
+        // and back the way a bot would read it, answering for the step it was sent
+        let sent: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(sent["snakes"][sent["you"].as_u64().unwrap() as usize]["body"][0], serde_json::json!([5, 3]));
+        let answer = format!(r#"{{"direction": "Up", "tick": {}}}"#, sent["tick"]);
+        assert_eq!(parse_reply(&answer), Some(Reply { direction: Direction::Up, tick: Some(game.tick()) }));
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+// Command line options, anything not given falls back to the defaults
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Args {
+    pub highscores: Option<PathBuf>,
+    pub seed: Option<u64>,
+    // a preset name or WIDTHxHEIGHT[@CELL_PX], checked once the settings are loaded
+    pub board: Option<String>,
+    // a difficulty name or a fixed move time in milliseconds
+    pub speed: Option<String>,
+    // wall mode, solid, wrap or clamp
+    pub mode: Option<String>,
+    // an AI strategy to play the first snake instead of the keyboard
+    pub ai: Option<String>,
+    pub debug: bool,
+    // no window, a run starts straight away and the game quits when it ends
+    pub headless: bool,
+    // a replay file to watch instead of playing
+    pub replay: Option<PathBuf>,
+    // a replay to race against
+    pub ghost: Option<PathBuf>,
+    pub print_default_config: bool,
+    // steer the first snake from another program, see snake_game::bot
+    pub bot_stdio: bool,
+    // how long to wait for the bot's answer each step, in milliseconds
+    pub bot_timeout: Option<u64>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        Args::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Self {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            // both "--flag value" and "--flag=value" work
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            let mut value = || inline_value.clone().or_else(|| args.next());
+
+            match flag.as_str() {
+                "--highscores" => match value() {
+                    Some(path) => parsed.highscores = Some(PathBuf::from(path)),
+                    None => eprintln!("--highscores needs a file path"),
+                },
+                "--seed" => match value().map(|seed| seed.parse::<u64>()) {
+                    Some(Ok(seed)) => parsed.seed = Some(seed),
+                    _ => eprintln!("--seed needs a whole number"),
+                },
+                "--board" => match value() {
+                    Some(board) => parsed.board = Some(board),
+                    None => eprintln!("--board needs a size, e.g. large or 40x30"),
+                },
+                "--speed" => match value() {
+                    Some(speed) => parsed.speed = Some(speed),
+                    None => eprintln!("--speed needs a difficulty or a move time in milliseconds"),
+                },
+                "--mode" => match value() {
+                    Some(mode) => parsed.mode = Some(mode),
+                    None => eprintln!("--mode needs solid, wrap or clamp"),
+                },
+                "--ai" => match value() {
+                    Some(ai) => parsed.ai = Some(ai),
+                    None => eprintln!("--ai needs greedy, astar or hamiltonian"),
+                },
+                "--debug" => parsed.debug = true,
+                "--replay" => match value() {
+                    Some(path) => parsed.replay = Some(PathBuf::from(path)),
+                    None => eprintln!("--replay needs a file path"),
+                },
+                "--ghost" => match value() {
+                    Some(path) => parsed.ghost = Some(PathBuf::from(path)),
+                    None => eprintln!("--ghost needs a replay file"),
+                },
+                "--headless" => parsed.headless = true,
+                "--bot-stdio" => parsed.bot_stdio = true,
+                "--bot-timeout" => match value().map(|ms| ms.parse::<u64>()) {
+                    Some(Ok(ms)) => parsed.bot_timeout = Some(ms),
+                    _ => eprintln!("--bot-timeout needs a whole number of milliseconds"),
+                },
+                "--print-default-config" => parsed.print_default_config = true,
+                _ => eprintln!("Ignoring unknown argument {}", arg),
+            }
+        }
+        parsed
+    }
+}
@@ -0,0 +1,564 @@
+use std::collections::BTreeMap;
+
+use bevy::input::gamepad::{GamepadConnection, GamepadConnectionEvent};
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::{ButtonState, InputSystem};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::debug::DebugMode;
+use crate::menu::{spawn_button, spawn_menu, MenuButton, MenuFocus};
+use crate::settings::{change_settings, Settings, SettingsFile};
+use crate::theme::ThemeColor;
+use crate::AppState;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ButtonInput<Action>>()
+            .init_resource::<PlayerActions>()
+            .init_resource::<Controllers>()
+            .init_resource::<Rebinding>()
+            .add_systems(PreUpdate, (assign_controllers, read_actions).chain().after(InputSystem))
+            .add_systems(OnEnter(AppState::Controls), spawn_controls)
+            .add_systems(OnExit(AppState::Controls), stop_rebinding)
+            .add_systems(
+                Update,
+                (binding_buttons, rebind_key, update_binding_labels).chain().run_if(in_state(AppState::Controls)),
+            );
+    }
+}
+
+// Everything the game reacts to goes through one of these. Menus read them from
+// ButtonInput<Action>, which has every player's input merged, while steering reads
+// each player's own from PlayerActions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Pause,
+    Confirm,
+    Back,
+    Fullscreen,
+    DebugGrow,
+    DebugShrink,
+    DebugFood,
+    DebugInvincible,
+    DebugStep,
+    DebugAutopilot,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Pause,
+        Action::Confirm,
+        Action::Back,
+        Action::Fullscreen,
+        Action::DebugGrow,
+        Action::DebugShrink,
+        Action::DebugFood,
+        Action::DebugInvincible,
+        Action::DebugStep,
+        Action::DebugAutopilot,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+            Action::Fullscreen => "Fullscreen",
+            Action::DebugGrow => "Grow",
+            Action::DebugShrink => "Shrink",
+            Action::DebugFood => "Food",
+            Action::DebugInvincible => "Invincible",
+            Action::DebugStep => "Step",
+            Action::DebugAutopilot => "Autopilot",
+        }
+    }
+
+    // cheats, these do nothing outside of debug mode
+    pub fn is_debug(self) -> bool {
+        matches!(
+            self,
+            Action::DebugGrow
+                | Action::DebugShrink
+                | Action::DebugFood
+                | Action::DebugInvincible
+                | Action::DebugStep
+                | Action::DebugAutopilot
+        )
+    }
+}
+
+// how many keys the controls screen lets you put on one action
+const BINDING_SLOTS: usize = 2;
+
+// Which keys or gamepad buttons trigger which action, saved as part of the settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bindings<T>(BTreeMap<Action, Vec<T>>);
+
+pub type KeyBindings = Bindings<KeyCode>;
+pub type GamepadBindings = Bindings<GamepadButtonType>;
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Bindings(BTreeMap::from([
+            (Action::Up, vec![KeyCode::KeyW, KeyCode::ArrowUp]),
+            (Action::Down, vec![KeyCode::KeyS, KeyCode::ArrowDown]),
+            (Action::Left, vec![KeyCode::KeyA, KeyCode::ArrowLeft]),
+            (Action::Right, vec![KeyCode::KeyD, KeyCode::ArrowRight]),
+            (Action::Pause, vec![KeyCode::Escape, KeyCode::KeyP]),
+            (Action::Confirm, vec![KeyCode::Enter]),
+            (Action::Back, vec![KeyCode::Escape]),
+            (Action::Fullscreen, vec![KeyCode::F11]),
+            (Action::DebugGrow, vec![KeyCode::Space]),
+            (Action::DebugShrink, vec![KeyCode::KeyX]),
+            (Action::DebugFood, vec![KeyCode::KeyF]),
+            (Action::DebugInvincible, vec![KeyCode::KeyI]),
+            (Action::DebugStep, vec![KeyCode::KeyN]),
+            (Action::DebugAutopilot, vec![KeyCode::KeyO]),
+        ]))
+    }
+}
+
+// the left stick is read separately, see stick_direction
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Bindings(BTreeMap::from([
+            (Action::Up, vec![GamepadButtonType::DPadUp]),
+            (Action::Down, vec![GamepadButtonType::DPadDown]),
+            (Action::Left, vec![GamepadButtonType::DPadLeft]),
+            (Action::Right, vec![GamepadButtonType::DPadRight]),
+            (Action::Pause, vec![GamepadButtonType::Start]),
+            (Action::Confirm, vec![GamepadButtonType::South]),
+            (Action::Back, vec![GamepadButtonType::East]),
+            (Action::DebugGrow, vec![GamepadButtonType::North]),
+        ]))
+    }
+}
+
+impl<T: Copy> Bindings<T> {
+    pub fn keys(&self, action: Action) -> &[T] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // put a key in one of an action's slots, or on the end if the slot is empty
+    pub fn set(&mut self, action: Action, slot: usize, key: T) {
+        let keys = self.0.entry(action).or_default();
+        match keys.get_mut(slot) {
+            Some(existing) => *existing = key,
+            None => keys.push(key),
+        }
+    }
+
+    pub fn clear(&mut self, action: Action, slot: usize) {
+        if let Some(keys) = self.0.get_mut(&action) {
+            if slot < keys.len() {
+                keys.remove(slot);
+            }
+        }
+    }
+}
+
+// shorter names than KeyCode's debug output, "KeyW" becomes "W"
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+// one for each snake, and the game only has the one
+pub const MAX_PLAYERS: usize = 1;
+
+// Each player's own actions, indexed the same as the sim's snakes
+#[derive(Resource)]
+pub struct PlayerActions(Vec<ButtonInput<Action>>);
+
+impl Default for PlayerActions {
+    fn default() -> Self {
+        PlayerActions(vec![ButtonInput::default(); MAX_PLAYERS])
+    }
+}
+
+impl PlayerActions {
+    pub fn player(&self, index: usize) -> Option<&ButtonInput<Action>> {
+        self.0.get(index)
+    }
+}
+
+// Which gamepad each player is holding. The keyboard always belongs to player one,
+// gamepads are handed to the first player without one as they get plugged in.
+#[derive(Resource)]
+pub struct Controllers(Vec<Option<Gamepad>>);
+
+impl Default for Controllers {
+    fn default() -> Self {
+        Controllers(vec![None; MAX_PLAYERS])
+    }
+}
+
+impl Controllers {
+    pub fn gamepad(&self, player: usize) -> Option<Gamepad> {
+        self.0.get(player).copied().flatten()
+    }
+}
+
+fn assign_controllers(mut connections: EventReader<GamepadConnectionEvent>, mut controllers: ResMut<Controllers>) {
+    for event in connections.read() {
+        let assigned = controllers.0.iter().position(|slot| *slot == Some(event.gamepad));
+        match (&event.connection, assigned) {
+            (GamepadConnection::Connected(info), None) => {
+                match controllers.0.iter().position(Option::is_none) {
+                    Some(player) => {
+                        controllers.0[player] = Some(event.gamepad);
+                        info!("{} is controlling player {}", info.name, player + 1);
+                    }
+                    None => info!("No free player for {}", info.name),
+                }
+            }
+            (GamepadConnection::Disconnected, Some(player)) => controllers.0[player] = None,
+            _ => {}
+        }
+    }
+}
+
+// the stick counts as whichever direction it's pushed furthest in once it's past the deadzone
+fn stick_direction(axes: &Axis<GamepadAxis>, gamepad: Gamepad, deadzone: f32) -> Option<Action> {
+    let x = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))?;
+    let y = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))?;
+    if x.abs().max(y.abs()) < deadzone {
+        return None;
+    }
+    Some(match (x.abs() > y.abs(), x > 0., y > 0.) {
+        (true, true, _) => Action::Right,
+        (true, false, _) => Action::Left,
+        (false, _, true) => Action::Up,
+        (false, _, false) => Action::Down,
+    })
+}
+
+fn read_actions(
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    settings: Res<Settings>,
+    controllers: Res<Controllers>,
+    mut player_actions: ResMut<PlayerActions>,
+    mut actions: ResMut<ButtonInput<Action>>,
+) {
+    for (player, player_actions) in player_actions.0.iter_mut().enumerate() {
+        let gamepad = controllers.gamepad(player);
+        let stick = gamepad.and_then(|gamepad| stick_direction(&axes, gamepad, settings.stick_deadzone));
+
+        player_actions.clear();
+        for action in Action::ALL {
+            let on_keyboard = player == 0 && keys.any_pressed(settings.keys.keys(action).iter().copied());
+            let on_gamepad = gamepad.is_some_and(|gamepad| {
+                stick == Some(action)
+                    || buttons.any_pressed(
+                        settings.buttons.keys(action).iter().map(|button| GamepadButton::new(gamepad, *button)),
+                    )
+            });
+            if on_keyboard || on_gamepad {
+                player_actions.press(action);
+            } else {
+                player_actions.release(action);
+            }
+        }
+    }
+
+    actions.clear();
+    for action in Action::ALL {
+        if player_actions.0.iter().any(|player_actions| player_actions.pressed(action)) {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+struct BindingSlot {
+    action: Action,
+    slot: usize,
+}
+
+// the slot waiting for a key press, if any
+#[derive(Resource, Default)]
+pub struct Rebinding(Option<BindingSlot>);
+
+// run condition for anything else that listens to the keys on the controls screen
+pub fn waiting_for_key(rebinding: Res<Rebinding>) -> bool {
+    rebinding.0.is_some()
+}
+
+fn spawn_controls(mut commands: Commands, debug: Res<DebugMode>) {
+    let menu = spawn_menu(
+        &mut commands,
+        "Controls",
+        &["Click a slot and press a key, Backspace clears it".to_string()],
+        &[],
+    );
+    commands.entity(menu).with_children(|parent| {
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(2.),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|rows| {
+                for action in Action::ALL.into_iter().filter(|action| debug.enabled || !action.is_debug()) {
+                    rows.spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(8.),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            TextBundle {
+                                style: Style {
+                                    width: Val::Px(110.),
+                                    ..default()
+                                },
+                                ..TextBundle::from_section(
+                                    action.name(),
+                                    TextStyle {
+                                        font_size: 20.,
+                                        ..default()
+                                    },
+                                )
+                            },
+                            ThemeColor::Text,
+                        ));
+                        for slot in 0..BINDING_SLOTS {
+                            spawn_button(row, "", 140., 24., BindingSlot { action, slot });
+                        }
+                    });
+                }
+            });
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|row| {
+                spawn_button(row, "Reset", 160., 40., MenuButton::ResetControls);
+                spawn_button(row, "Back", 160., 40., MenuButton::Options);
+            });
+    });
+}
+
+fn stop_rebinding(mut rebinding: ResMut<Rebinding>) {
+    rebinding.0 = None;
+}
+
+// A slot can be clicked, or picked with Up and Down and confirmed. Either way it's the
+// picked one afterwards, so Enter doesn't press some other button while it waits.
+fn binding_buttons(
+    interaction_query: Query<(Entity, Ref<Interaction>, &BindingSlot)>,
+    actions: Res<ButtonInput<Action>>,
+    mut focus: ResMut<MenuFocus>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for (entity, interaction, slot) in &interaction_query {
+        let clicked = interaction.is_changed() && *interaction == Interaction::Pressed;
+        let confirmed = rebinding.0.is_none() && focus.confirmed(entity, &actions);
+        if clicked || confirmed {
+            rebinding.0 = Some(*slot);
+            focus.0 = Some(entity);
+        }
+    }
+}
+
+// While a slot is waiting the next key press goes into it and nothing else sees it,
+// Escape gives up on it. Otherwise Back leaves the screen like any other menu.
+fn rebind_key(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+    mut settings_file: ResMut<SettingsFile>,
+    actions: Res<ButtonInput<Action>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let Some(slot) = rebinding.0 else {
+        keyboard_events.clear();
+        if actions.just_pressed(Action::Back) {
+            next_state.set(AppState::Options);
+        }
+        return;
+    };
+    // the key that picked the slot this frame isn't the one to put in it
+    if rebinding.is_changed() {
+        keyboard_events.clear();
+        return;
+    }
+
+    let Some(key) = keyboard_events.read().find(|event| event.state == ButtonState::Pressed).map(|event| event.key_code)
+    else {
+        return;
+    };
+    rebinding.0 = None;
+    match key {
+        KeyCode::Escape => {}
+        KeyCode::Backspace | KeyCode::Delete => {
+            change_settings(&mut settings, &mut settings_file, |settings| settings.keys.clear(slot.action, slot.slot))
+        }
+        key => change_settings(&mut settings, &mut settings_file, |settings| settings.keys.set(slot.action, slot.slot, key)),
+    }
+}
+
+fn update_binding_labels(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    buttons: Query<(Ref<BindingSlot>, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (slot, children) in &buttons {
+        if !settings.is_changed() && !rebinding.is_changed() && !slot.is_added() {
+            continue;
+        }
+        let label = if rebinding.0 == Some(*slot) {
+            "Press a key".to_string()
+        } else {
+            settings.keys.keys(slot.action).get(slot.slot).map_or("-".to_string(), |key| key_name(*key))
+        };
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::gamepad::{
+        GamepadAxisChangedEvent, GamepadButtonChangedEvent, GamepadConnection, GamepadConnectionEvent, GamepadEvent,
+        GamepadInfo,
+    };
+    use bevy::input::InputPlugin;
+
+    use super::*;
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin))
+            .init_state::<AppState>()
+            .insert_resource(Settings::default())
+            .add_plugins(ControlsPlugin);
+        app
+    }
+
+    fn send(app: &mut App, event: GamepadEvent) {
+        app.world.send_event(event);
+        app.update();
+    }
+
+    fn connect(app: &mut App, id: usize) -> Gamepad {
+        let gamepad = Gamepad::new(id);
+        let info = GamepadInfo { name: format!("Test pad {}", id) };
+        send(app, GamepadEvent::Connection(GamepadConnectionEvent::new(gamepad, GamepadConnection::Connected(info))));
+        gamepad
+    }
+
+    fn button(app: &mut App, gamepad: Gamepad, button: GamepadButtonType, value: f32) {
+        send(app, GamepadEvent::Button(GamepadButtonChangedEvent::new(gamepad, button, value)));
+    }
+
+    fn stick(app: &mut App, gamepad: Gamepad, axis: GamepadAxisType, value: f32) {
+        send(app, GamepadEvent::Axis(GamepadAxisChangedEvent::new(gamepad, axis, value)));
+    }
+
+    fn player(app: &App, index: usize) -> &ButtonInput<Action> {
+        app.world.resource::<PlayerActions>().player(index).unwrap()
+    }
+
+    #[test]
+    fn dpad_steers_the_assigned_player() {
+        let mut app = test_app();
+        let gamepad = connect(&mut app, 0);
+        assert_eq!(app.world.resource::<Controllers>().gamepad(0), Some(gamepad));
+
+        button(&mut app, gamepad, GamepadButtonType::DPadLeft, 1.);
+        assert!(player(&app, 0).just_pressed(Action::Left));
+        assert!(app.world.resource::<ButtonInput<Action>>().just_pressed(Action::Left));
+
+        // held down it's still pressed but no longer just pressed
+        app.update();
+        assert!(player(&app, 0).pressed(Action::Left));
+        assert!(!player(&app, 0).just_pressed(Action::Left));
+        assert!(!app.world.resource::<ButtonInput<Action>>().just_pressed(Action::Left));
+
+        button(&mut app, gamepad, GamepadButtonType::DPadLeft, 0.);
+        assert!(player(&app, 0).just_released(Action::Left));
+    }
+
+    #[test]
+    fn stick_ignores_small_pushes() {
+        let mut app = test_app();
+        let gamepad = connect(&mut app, 0);
+
+        stick(&mut app, gamepad, GamepadAxisType::LeftStickX, 0.3);
+        assert!(player(&app, 0).get_pressed().next().is_none());
+
+        stick(&mut app, gamepad, GamepadAxisType::LeftStickX, 0.9);
+        assert!(player(&app, 0).just_pressed(Action::Right));
+
+        // pushed further down than right counts as down only
+        stick(&mut app, gamepad, GamepadAxisType::LeftStickY, -1.);
+        assert!(player(&app, 0).just_pressed(Action::Down));
+        assert!(!player(&app, 0).pressed(Action::Right));
+    }
+
+    #[test]
+    fn no_player_for_a_second_gamepad() {
+        let mut app = test_app();
+        let first = connect(&mut app, 0);
+        let second = connect(&mut app, 1);
+        assert_eq!(app.world.resource::<Controllers>().gamepad(0), Some(first));
+        assert!(app.world.resource::<PlayerActions>().player(1).is_none());
+
+        button(&mut app, second, GamepadButtonType::South, 1.);
+        assert!(!player(&app, 0).pressed(Action::Confirm));
+        assert!(!app.world.resource::<ButtonInput<Action>>().pressed(Action::Confirm));
+
+        // menus listen to the one that has a player
+        button(&mut app, first, GamepadButtonType::South, 1.);
+        assert!(app.world.resource::<ButtonInput<Action>>().just_pressed(Action::Confirm));
+    }
+
+    #[test]
+    fn unplugged_gamepad_frees_its_player() {
+        let mut app = test_app();
+        let first = connect(&mut app, 0);
+        send(&mut app, GamepadEvent::Connection(GamepadConnectionEvent::new(first, GamepadConnection::Disconnected)));
+        assert_eq!(app.world.resource::<Controllers>().gamepad(0), None);
+
+        let replacement = connect(&mut app, 2);
+        assert_eq!(app.world.resource::<Controllers>().gamepad(0), Some(replacement));
+    }
+}
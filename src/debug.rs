@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use snake_game::sim::Cell;
+
+use crate::board::BoardConfig;
+use crate::controls::Action;
+use crate::settings::Settings;
+use crate::snake::{PlayerInput, Sim, SimEvent, StepOnce};
+use crate::theme::{CurrentTheme, ThemeColor};
+use crate::AppState;
+
+// Everything in here is for poking at the game while working on it. None of it runs
+// unless debug mode is on, either with --debug or from the main menu.
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_overlay)
+            .add_systems(Update, show_overlay.run_if(resource_changed::<DebugMode>))
+            .add_systems(
+                Update,
+                (
+                    (draw_grid, update_overlay).run_if(in_state(AppState::Playing).or_else(in_state(AppState::Paused))),
+                    (spawn_snake_body_test, shrink_snake, place_food, toggle_invincible, toggle_autopilot)
+                        .run_if(in_state(AppState::Playing).or_else(in_state(AppState::Paused))),
+                    step_while_paused.run_if(in_state(AppState::Paused)),
+                )
+                    .run_if(debug_enabled),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct DebugMode {
+    pub enabled: bool,
+}
+
+fn debug_enabled(debug: Res<DebugMode>) -> bool {
+    debug.enabled
+}
+
+#[derive(Component)]
+struct DebugOverlay;
+
+#[derive(Component)]
+struct DebugText;
+
+fn spawn_overlay(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(4.),
+                    right: Val::Px(8.),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            DebugOverlay,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 16.,
+                        ..default()
+                    },
+                ),
+                DebugText,
+                ThemeColor::Accent,
+            ));
+        });
+}
+
+fn show_overlay(debug: Res<DebugMode>, mut overlay: Query<&mut Visibility, With<DebugOverlay>>) {
+    for mut visibility in &mut overlay {
+        *visibility = if debug.enabled { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+fn draw_grid(board_config: Res<BoardConfig>, theme: Res<CurrentTheme>, mut gizmos: Gizmos) {
+    let half = Vec2::new(board_config.width_px(), board_config.height_px()) / 2.;
+    for x in 0..=board_config.width_cells {
+        let x = x as f32 * board_config.cell_px - half.x;
+        gizmos.line_2d(Vec2::new(x, -half.y), Vec2::new(x, half.y), theme.grid);
+    }
+    for y in 0..=board_config.height_cells {
+        let y = y as f32 * board_config.cell_px - half.y;
+        gizmos.line_2d(Vec2::new(-half.x, y), Vec2::new(half.x, y), theme.grid);
+    }
+}
+
+// the cell under the mouse, None when the mouse is outside the window
+fn cursor_cell(
+    window: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Query<(&Camera, &GlobalTransform)>,
+    board_config: &BoardConfig,
+) -> Option<Cell> {
+    let cursor = window.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera.get_single().ok()?;
+    let position = camera.viewport_to_world_2d(camera_transform, cursor)?;
+    Some(board_config.world_to_cell(position))
+}
+
+fn update_overlay(
+    sim: Res<Sim>,
+    player_input: Res<PlayerInput>,
+    board_config: Res<BoardConfig>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    entities: Query<Entity>,
+    mut text: Query<&mut Text, With<DebugText>>,
+) {
+    let snake = &sim.snakes()[0];
+    let queue: Vec<String> = player_input
+        .turns(0)
+        .map(|turns| turns.turns().map(|direction| format!("{:?}", direction)).collect())
+        .unwrap_or_default();
+    let cursor = match cursor_cell(&window, &camera, &board_config) {
+        Some(cell) => format!("{}, {}", cell.x, cell.y),
+        None => "-".to_string(),
+    };
+
+    let lines = [
+        format!("Tick: {}", sim.tick()),
+        format!("Head: {}, {} {:?}", snake.head().x, snake.head().y, snake.direction()),
+        format!("Queue: [{}]", queue.join(", ")),
+        format!("Cursor: {}", cursor),
+        format!("Entities: {}", entities.iter().count()),
+        format!("Invincible: {}", snake.is_invincible()),
+        format!("Pilot: {}{}", player_input.pilot_name(0), if player_input.autopilot().is_some() { " (autopilot)" } else { "" }),
+    ];
+    for mut text in &mut text {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn spawn_snake_body_test(
+    actions: Res<ButtonInput<Action>>,
+    mut sim: ResMut<Sim>,
+) {
+    if actions.just_pressed(Action::DebugGrow) {
+        sim.grow(0);
+    }
+}
+
+fn shrink_snake(actions: Res<ButtonInput<Action>>, mut sim: ResMut<Sim>, mut sim_events: EventWriter<SimEvent>) {
+    if actions.just_pressed(Action::DebugShrink) {
+        if let Some(event) = sim.shrink(0) {
+            sim_events.send(SimEvent(event));
+        }
+    }
+}
+
+fn place_food(
+    actions: Res<ButtonInput<Action>>,
+    mut sim: ResMut<Sim>,
+    board_config: Res<BoardConfig>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+) {
+    if !actions.just_pressed(Action::DebugFood) {
+        return;
+    }
+    if let Some(cell) = cursor_cell(&window, &camera, &board_config) {
+        if !sim.place_food(cell) {
+            eprintln!("Can't put food at {}, {}", cell.x, cell.y);
+        }
+    }
+}
+
+fn toggle_invincible(actions: Res<ButtonInput<Action>>, mut sim: ResMut<Sim>) {
+    if actions.just_pressed(Action::DebugInvincible) {
+        let invincible = sim.snakes()[0].is_invincible();
+        sim.set_invincible(0, !invincible);
+    }
+}
+
+// O hands the snake to the AI and back
+fn toggle_autopilot(actions: Res<ButtonInput<Action>>, settings: Res<Settings>, mut player_input: ResMut<PlayerInput>) {
+    if actions.just_pressed(Action::DebugAutopilot) {
+        let autopilot = match player_input.autopilot() {
+            Some(_) => None,
+            None => Some(settings.autopilot),
+        };
+        player_input.set_autopilot(autopilot, settings.pilot);
+    }
+}
+
+fn step_while_paused(mut commands: Commands, actions: Res<ButtonInput<Action>>) {
+    if actions.just_pressed(Action::DebugStep) {
+        commands.insert_resource(StepOnce);
+    }
+}
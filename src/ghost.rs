@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use snake_game::replay::{Replay, ReplaySettings};
+use snake_game::sim::GameState;
+
+use crate::board::{BoardConfig, GridPos, Motion};
+use crate::leaderboard::HighScores;
+use crate::replays::Playback;
+use crate::snake::{move_snake, RunStarted, Sim, SpeedProgression};
+use crate::theme::CurrentTheme;
+
+// Races the player against their best run on the same seed. The ghost is a second copy of
+// the sim following the recorded turns, so it can't get in the player's way.
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, pick_ghost.run_if(not(resource_exists::<Playback>)))
+            .add_systems(FixedUpdate, (step_ghost.after(move_snake), sync_ghost.after(step_ghost)));
+    }
+}
+
+// how see-through the ghost is drawn
+const GHOST_ALPHA: f32 = 0.35;
+
+// The run given with --ghost, raced whenever its seed and settings come up
+#[derive(Resource)]
+pub struct GhostRun(pub Replay);
+
+#[derive(Resource)]
+pub struct Ghost {
+    replay: Replay,
+    game: GameState,
+    // false once the recorded run has ended
+    running: bool,
+}
+
+impl Ghost {
+    pub fn score(&self) -> u32 {
+        self.game.score()
+    }
+}
+
+#[derive(Component)]
+struct GhostSegment(usize);
+
+// a best run for each board, seed and rules, kept next to the high score table
+fn ghost_path(dir: &Path, settings: &ReplaySettings, seed: u64) -> PathBuf {
+    dir.join(format!("{}.ron", settings.name(seed)))
+}
+
+// Saves a finished run as the one to race if it beat the last best for its seed
+pub fn keep_if_best(replay: &Replay, high_scores: &HighScores) {
+    let Some(dir) = high_scores.ghost_dir() else { return };
+    let path = ghost_path(&dir, &replay.settings, replay.seed);
+    let best = Replay::load(&path).ok().filter(|best| best.settings == replay.settings);
+    if best.is_some_and(|best| !replay.beats(&best)) {
+        return;
+    }
+    match replay.save(&path) {
+        Ok(()) => eprintln!("New best run for seed {}, saved to {}", replay.seed, path.display()),
+        Err(err) => error!("Ghost: {}", err),
+    }
+}
+
+// At the start of each run, find something to race: the --ghost run if it's for this seed,
+// otherwise the best run saved for it
+fn pick_ghost(
+    mut commands: Commands,
+    mut run_started: EventReader<RunStarted>,
+    sim: Res<Sim>,
+    speed: Res<SpeedProgression>,
+    high_scores: Res<HighScores>,
+    ghost_run: Option<Res<GhostRun>>,
+) {
+    if run_started.read().count() == 0 {
+        return;
+    }
+    let settings = ReplaySettings::new(sim.config(), (**speed).clone());
+    let matches = |replay: &Replay| replay.seed == sim.seed() && replay.settings == settings;
+    let replay = ghost_run.map(|run| run.0.clone()).filter(matches).or_else(|| {
+        let dir = high_scores.ghost_dir()?;
+        Replay::load(&ghost_path(&dir, &settings, sim.seed())).ok().filter(matches)
+    });
+    match replay {
+        Some(replay) => {
+            eprintln!("Racing a ghost that scored {}", replay.final_score);
+            commands.insert_resource(Ghost {
+                game: replay.start(),
+                replay,
+                running: true,
+            });
+        }
+        None => commands.remove_resource::<Ghost>(),
+    }
+}
+
+// keeps the ghost on the same tick as the player
+fn step_ghost(sim: Res<Sim>, ghost: Option<ResMut<Ghost>>) {
+    let Some(mut ghost) = ghost else { return };
+    if ghost.running && ghost.game.tick() < sim.tick() {
+        let ghost = &mut *ghost;
+        ghost.running = ghost.replay.follow(&mut ghost.game, sim.tick());
+    }
+}
+
+fn sync_ghost(
+    mut commands: Commands,
+    ghost: Option<Res<Ghost>>,
+    board_config: Res<BoardConfig>,
+    theme: Res<CurrentTheme>,
+    mut segments: Query<(Entity, &GhostSegment, &mut GridPos, &mut Motion, &mut Sprite)>,
+) {
+    // the ghost disappears once its run is over
+    let body = match &ghost {
+        Some(ghost) if ghost.running => ghost.game.snakes()[0].body().clone(),
+        _ => Default::default(),
+    };
+    let tick = ghost.as_ref().map_or(0, |ghost| ghost.game.tick());
+
+    let mut count = 0;
+    for (entity, segment, mut grid_pos, mut motion, mut sprite) in &mut segments {
+        if theme.is_changed() {
+            sprite.color = theme.snake_head.with_a(GHOST_ALPHA);
+        }
+        match body.get(segment.0) {
+            Some(cell) => {
+                if motion.tick != tick {
+                    *motion = Motion::at(*grid_pos, tick);
+                }
+                grid_pos.set_if_neq((*cell).into());
+                count += 1;
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+    for (index, cell) in body.iter().enumerate().skip(count) {
+        let grid_pos = GridPos::from(*cell);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: theme.snake_head.with_a(GHOST_ALPHA),
+                    custom_size: Some(Vec2::splat(board_config.cell_px)),
+                    ..default()
+                },
+                // under the player's snake
+                transform: Transform::from_xyz(0., 0., 0.4),
+                ..default()
+            },
+            grid_pos,
+            Motion::at(grid_pos, tick),
+            GhostSegment(index),
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::snake::{RunClock, Sim};
+use crate::AppState;
+
+// For --headless. There's no window to click through the menus with, so a run starts
+// straight away and the game quits once it's over, printing how it went.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, start_run)
+            .add_systems(OnEnter(AppState::GameOver), finish_run)
+            .add_systems(OnEnter(AppState::EnterName), finish_run);
+    }
+}
+
+fn start_run(mut next_state: ResMut<NextState<AppState>>) {
+    next_state.set(AppState::Playing);
+}
+
+fn finish_run(sim: Res<Sim>, run_clock: Res<RunClock>, mut exit: EventWriter<AppExit>) {
+    let snake = &sim.snakes()[0];
+    let death = snake.death().map_or("Board full", |cause| cause.describe());
+    eprintln!(
+        "Score: {}, length: {}, ticks: {}, time: {:.1}s, seed: {}, {}",
+        sim.score(),
+        snake.length(),
+        sim.tick(),
+        run_clock.0.elapsed_secs(),
+        sim.seed(),
+        death
+    );
+    exit.send(AppExit);
+}
@@ -0,0 +1,62 @@
+// Local high score table, stored as a RON file
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, StorageError};
+
+pub const MAX_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub score: u32,
+    pub length: u32,
+    pub duration: Duration,
+    pub mode: String,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScoreTable {
+    pub entries: Vec<HighScoreEntry>,   // best first
+}
+
+// where the table lives if nothing else was asked for
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("snake_game").join("highscores.ron"))
+}
+
+impl HighScoreTable {
+    // A missing file is just an empty table, anything else that goes wrong is an error
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        let mut table: HighScoreTable = storage::load_ron(path)?;
+        table.sort();
+        Ok(table)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        storage::save_ron(self, path)
+    }
+
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.entries.len() < MAX_ENTRIES || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    // Returns the place the entry ended up in, or None if it didn't make the table
+    pub fn insert(&mut self, entry: HighScoreEntry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self.entries.iter().position(|other| entry.score > other.score).unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+
+use snake_game::sim::GameEvent;
+
+use crate::ghost::Ghost;
+use crate::snake::{MoveTime, RunClock, RunStarted, Sim, SimEvent};
+use crate::theme::ThemeColor;
+use crate::AppState;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud)
+            .add_systems(OnEnter(AppState::MainMenu), hide_hud)
+            .add_systems(OnEnter(AppState::Playing), show_hud)
+            .add_systems(
+                Update,
+                (update_score_and_length, update_speed, update_time, update_ghost_delta),
+            );
+    }
+}
+
+#[derive(Component)]
+struct Hud;
+
+// which part of the hud a text section shows
+#[derive(Component)]
+enum HudValue {
+    Score,
+    Length,
+    Lives,
+    Time,
+    Speed,
+    Ghost,
+}
+
+fn spawn_hud(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: 18.,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(4.),
+                    left: Val::Px(8.),
+                    column_gap: Val::Px(16.),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Hud,
+        ))
+        .with_children(|parent| {
+            for (label, value) in [
+                ("Score: ", HudValue::Score),
+                ("Length: ", HudValue::Length),
+                ("Lives: ", HudValue::Lives),
+                ("Time: ", HudValue::Time),
+                ("Speed: ", HudValue::Speed),
+                ("", HudValue::Ghost),
+            ] {
+                parent.spawn((
+                    TextBundle::from_sections([
+                        TextSection::new(label, text_style.clone()),
+                        TextSection::new("0", text_style.clone()),
+                    ]),
+                    value,
+                    ThemeColor::Text,
+                ));
+            }
+        });
+}
+
+fn hide_hud(mut hud: Query<&mut Visibility, With<Hud>>) {
+    for mut visibility in &mut hud {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn show_hud(mut hud: Query<&mut Visibility, With<Hud>>) {
+    for mut visibility in &mut hud {
+        *visibility = Visibility::Inherited;
+    }
+}
+
+fn update_score_and_length(
+    mut sim_events: EventReader<SimEvent>,
+    mut run_started: EventReader<RunStarted>,
+    sim: Res<Sim>,
+    mut texts: Query<(&HudValue, &mut Text)>,
+) {
+    let mut changed = run_started.read().count() > 0;
+    for SimEvent(event) in sim_events.read() {
+        if let GameEvent::FoodEaten { .. }
+        | GameEvent::Grew { .. }
+        | GameEvent::SnakeDied { .. }
+        | GameEvent::Respawned { .. }
+        | GameEvent::TailReset { .. }
+        | GameEvent::Shrank { .. } = event
+        {
+            changed = true;
+        }
+    }
+    if !changed {
+        return;
+    }
+
+    let length = sim.snakes()[0].length();
+    for (value, mut text) in &mut texts {
+        match value {
+            HudValue::Score => text.sections[1].value = sim.score().to_string(),
+            HudValue::Length => text.sections[1].value = length.to_string(),
+            HudValue::Lives => text.sections[1].value = sim.snakes()[0].lives().to_string(),
+            HudValue::Time | HudValue::Speed | HudValue::Ghost => {}
+        }
+    }
+}
+
+// in moves per second, the timer changes every frame so only touch the text when the
+// number shown would be different
+fn update_speed(
+    move_time: Res<MoveTime>,
+    mut texts: Query<(&HudValue, &mut Text)>,
+) {
+    let speed = format!("{:.1}", 1. / move_time.timer.duration().as_secs_f32());
+    for (value, mut text) in &mut texts {
+        if let HudValue::Speed = value {
+            if text.sections[1].value != speed {
+                text.sections[1].value = speed.clone();
+            }
+        }
+    }
+}
+
+// only touches the text when the displayed second actually changes
+fn update_time(
+    run_clock: Res<RunClock>,
+    mut texts: Query<(&HudValue, &mut Text)>,
+) {
+    if !run_clock.is_changed() {
+        return;
+    }
+    let seconds = run_clock.0.elapsed().as_secs();
+    let elapsed = format!("{}:{:02}", seconds / 60, seconds % 60);
+    for (value, mut text) in &mut texts {
+        if let HudValue::Time = value {
+            if text.sections[1].value != elapsed {
+                text.sections[1].value = elapsed.clone();
+            }
+        }
+    }
+}
+
+// how far ahead of the ghost the player is, blank when there's no ghost to race
+fn update_ghost_delta(ghost: Option<Res<Ghost>>, sim: Res<Sim>, mut texts: Query<(&HudValue, &mut Text)>) {
+    let (label, delta) = match ghost {
+        Some(ghost) => ("Ghost: ", format!("{:+}", sim.score() as i64 - ghost.score() as i64)),
+        None => ("", String::new()),
+    };
+    for (value, mut text) in &mut texts {
+        if let HudValue::Ghost = value {
+            if text.sections[0].value != label || text.sections[1].value != delta {
+                text.sections[0].value = label.to_string();
+                text.sections[1].value = delta.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+
+use snake_game::highscore::{self, HighScoreEntry, HighScoreTable};
+use snake_game::storage::StorageError;
+
+use crate::cli::Args;
+use crate::debug::DebugMode;
+use crate::menu::{spawn_menu, MenuButton};
+use crate::settings::Settings;
+use crate::snake::{RunClock, Sim};
+use crate::theme::ThemeColor;
+use crate::AppState;
+
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerName>()
+            .add_systems(Startup, load_high_scores)
+            .add_systems(OnEnter(AppState::EnterName), spawn_name_entry)
+            .add_systems(OnEnter(AppState::Leaderboard), spawn_leaderboard)
+            .add_systems(Update, enter_name.run_if(in_state(AppState::EnterName)));
+    }
+}
+
+const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Resource)]
+pub struct HighScores {
+    table: HighScoreTable,
+    path: Option<PathBuf>,
+    // shown on the leaderboard so a broken file doesn't just look empty
+    error: Option<String>,
+}
+
+impl HighScores {
+    // best runs to race against are kept in a folder beside the table
+    pub fn ghost_dir(&self) -> Option<PathBuf> {
+        Some(self.path.as_ref()?.parent()?.join("ghosts"))
+    }
+
+    // and every finished run goes in another
+    pub fn replay_dir(&self) -> Option<PathBuf> {
+        Some(self.path.as_ref()?.parent()?.join("replays"))
+    }
+
+    fn report(&mut self, err: StorageError) {
+        error!("High scores: {}", err);
+        self.error = Some(err.to_string());
+    }
+}
+
+// name being typed in, kept between runs so it only has to be typed once
+#[derive(Resource, Default)]
+struct PlayerName(String);
+
+#[derive(Component)]
+struct NameText;
+
+// where to go once a run is over. Runs played with cheats available don't make the table,
+// nor do `unranked` ones, replays and runs the AI played
+pub fn game_over_state(high_scores: &HighScores, debug: &DebugMode, unranked: bool, score: u32) -> AppState {
+    if !debug.enabled && !unranked && high_scores.table.qualifies(score) {
+        AppState::EnterName
+    } else {
+        AppState::GameOver
+    }
+}
+
+fn load_high_scores(mut commands: Commands, args: Res<Args>) {
+    let path = args.highscores.clone().or_else(highscore::default_path);
+    let mut high_scores = HighScores {
+        table: HighScoreTable::default(),
+        path: path.clone(),
+        error: None,
+    };
+
+    match path {
+        Some(path) => match HighScoreTable::load(&path) {
+            Ok(table) => high_scores.table = table,
+            Err(err) => {
+                // keep the broken file around instead of overwriting it on the next save
+                if let StorageError::Parse(..) = err {
+                    let backup = path.with_extension("ron.bak");
+                    if std::fs::rename(&path, &backup).is_ok() {
+                        warn!("Moved unreadable high score file to {}", backup.display());
+                    }
+                }
+                high_scores.report(err);
+            }
+        },
+        None => {
+            high_scores.error = Some("No data directory found, high scores won't be saved".to_string());
+        }
+    }
+    commands.insert_resource(high_scores);
+}
+
+fn spawn_name_entry(mut commands: Commands, sim: Res<Sim>, player_name: Res<PlayerName>) {
+    let menu = spawn_menu(
+        &mut commands,
+        "New High Score!",
+        &[format!("Score: {}", sim.score()), "Type your name and press Enter".to_string()],
+        &[],
+    );
+    commands.entity(menu).with_children(|parent| {
+        parent.spawn((
+            TextBundle::from_section(
+                format!("{}_", player_name.0),
+                TextStyle {
+                    font_size: 32.,
+                    ..default()
+                },
+            ),
+            NameText,
+            ThemeColor::Accent,
+        ));
+    });
+}
+
+fn enter_name(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut player_name: ResMut<PlayerName>,
+    mut high_scores: ResMut<HighScores>,
+    sim: Res<Sim>,
+    run_clock: Res<RunClock>,
+    settings: Res<Settings>,
+    mut name_text: Query<&mut Text, With<NameText>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for character in characters.read() {
+        for c in character.char.chars() {
+            if !c.is_control() && player_name.0.chars().count() < MAX_NAME_LENGTH {
+                player_name.0.push(c);
+            }
+        }
+    }
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match event.key_code {
+            KeyCode::Backspace => {
+                player_name.0.pop();
+            }
+            KeyCode::Enter => {
+                let name = player_name.0.trim();
+                let entry = HighScoreEntry {
+                    name: if name.is_empty() { "Player".to_string() } else { name.to_string() },
+                    score: sim.score(),
+                    length: sim.snakes()[0].length() as u32,
+                    duration: run_clock.0.elapsed(),
+                    mode: format!("{} {}", sim.config().wall_mode.name(), settings.speed_name()),
+                    seed: sim.seed(),
+                };
+                high_scores.table.insert(entry);
+                if let Some(path) = high_scores.path.clone() {
+                    if let Err(err) = high_scores.table.save(&path) {
+                        high_scores.report(err);
+                    }
+                }
+                next_state.set(AppState::GameOver);
+                return;
+            }
+            _ => {}
+        }
+    }
+
+    if player_name.is_changed() {
+        for mut text in &mut name_text {
+            text.sections[0].value = format!("{}_", player_name.0);
+        }
+    }
+}
+
+fn spawn_leaderboard(mut commands: Commands, high_scores: Res<HighScores>) {
+    let mut lines: Vec<String> = high_scores
+        .table
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(rank, entry)| {
+            let seconds = entry.duration.as_secs();
+            format!(
+                "{:>2}. {:<12} {:>5}  len {:<4} {}:{:02}  {}  seed {}",
+                rank + 1,
+                entry.name,
+                entry.score,
+                entry.length,
+                seconds / 60,
+                seconds % 60,
+                entry.mode,
+                entry.seed,
+            )
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push("No high scores yet".to_string());
+    }
+    if let Some(error) = &high_scores.error {
+        lines.push(error.clone());
+    }
+
+    spawn_menu(&mut commands, "High Scores", &lines, &[("Back", MenuButton::Back)]);
+}
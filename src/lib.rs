@@ -0,0 +1,7 @@
+// Game rules that don't depend on bevy, so they can be run without a window
+pub mod ai;
+pub mod bot;
+pub mod highscore;
+pub mod replay;
+pub mod sim;
+pub mod storage;
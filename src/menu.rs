@@ -0,0 +1,470 @@
+use std::cmp::Ordering;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::board::BoardConfig;
+use crate::controls::{waiting_for_key, Action, KeyBindings};
+use crate::debug::DebugMode;
+use crate::replays::Playback;
+use crate::settings::{change_settings, Settings, SettingsFile};
+use crate::skin::Skins;
+use crate::snake::Sim;
+use crate::theme::{theme_names, CurrentTheme, Theme, ThemeColor};
+use crate::AppState;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuFocus>()
+            .add_systems(OnEnter(AppState::MainMenu), spawn_main_menu)
+            .add_systems(OnEnter(AppState::Options), spawn_options_menu)
+            .add_systems(OnEnter(AppState::Paused), spawn_pause_menu)
+            .add_systems(OnEnter(AppState::GameOver), spawn_game_over_menu)
+            .add_systems(OnExit(AppState::MainMenu), despawn_menu)
+            .add_systems(OnExit(AppState::Options), despawn_menu)
+            .add_systems(OnExit(AppState::Paused), despawn_menu)
+            .add_systems(OnExit(AppState::GameOver), despawn_menu)
+            .add_systems(OnExit(AppState::EnterName), despawn_menu)
+            .add_systems(OnExit(AppState::Leaderboard), despawn_menu)
+            .add_systems(OnExit(AppState::Controls), despawn_menu)
+            .add_systems(
+                Update,
+                (
+                    move_focus.run_if(not(waiting_for_key)),
+                    (button_colors, menu_buttons, menu_keys, toggle_pause, update_option_labels),
+                )
+                    .chain(),
+            );
+    }
+}
+
+// everything spawned for a menu gets this so it can be cleaned up in one go
+#[derive(Component)]
+struct MenuScreen;
+
+// The button Up and Down have moved to, so the menus work from a gamepad. Confirm presses
+// it. Nothing is picked until Up or Down is first pressed on a screen.
+#[derive(Resource, Default)]
+pub struct MenuFocus(pub Option<Entity>);
+
+impl MenuFocus {
+    // Confirm was just pressed with `button` picked
+    pub fn confirmed(&self, button: Entity, actions: &ButtonInput<Action>) -> bool {
+        self.0 == Some(button) && actions.just_pressed(Action::Confirm)
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+pub enum MenuButton {
+    Start,
+    Resume,
+    Restart,
+    HighScores,
+    Options,
+    Board,
+    WallMode,
+    Difficulty,
+    Skin,
+    Theme,
+    Debug,
+    Controls,
+    ResetControls,
+    Back,
+    Quit,
+}
+
+// Spawns a full screen menu and returns the root node so callers can add to it
+pub fn spawn_menu(commands: &mut Commands, title: &str, lines: &[String], buttons: &[(&str, MenuButton)]) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(8.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+            MenuScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font_size: 48.,
+                        ..default()
+                    },
+                ),
+                ThemeColor::Text,
+            ));
+            for line in lines {
+                parent.spawn((
+                    TextBundle::from_section(
+                        line.clone(),
+                        TextStyle {
+                            font_size: 24.,
+                            ..default()
+                        },
+                    ),
+                    ThemeColor::Text,
+                ));
+            }
+            for (label, action) in buttons {
+                spawn_button(parent, label, 180., 34., *action);
+            }
+        })
+        .id()
+}
+
+// a button with a text label, whatever is in `marker` says what it does. The text shrinks
+// to fit short buttons.
+pub fn spawn_button(parent: &mut ChildBuilder, label: &str, width: f32, height: f32, marker: impl Bundle) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(height),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                // coloured in by button_colors once it's spawned
+                ..default()
+            },
+            marker,
+        ))
+        .with_children(|button| {
+            button.spawn((
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: (height * 0.75).min(24.),
+                        ..default()
+                    },
+                ),
+                ThemeColor::Text,
+            ));
+        });
+}
+
+fn spawn_main_menu(mut commands: Commands) {
+    spawn_menu(
+        &mut commands,
+        "Snake",
+        &["Enter to start, Escape to pause".to_string()],
+        &[
+            ("Start", MenuButton::Start),
+            ("Options", MenuButton::Options),
+            ("High Scores", MenuButton::HighScores),
+            ("Quit", MenuButton::Quit),
+        ],
+    );
+}
+
+fn spawn_options_menu(mut commands: Commands) {
+    spawn_menu(
+        &mut commands,
+        "Options",
+        &[],
+        &[
+            ("Board", MenuButton::Board),
+            ("Walls", MenuButton::WallMode),
+            ("Difficulty", MenuButton::Difficulty),
+            ("Skin", MenuButton::Skin),
+            ("Theme", MenuButton::Theme),
+            ("Controls", MenuButton::Controls),
+            ("Debug", MenuButton::Debug),
+            ("Back", MenuButton::Back),
+        ],
+    );
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+    spawn_menu(
+        &mut commands,
+        "Paused",
+        &[],
+        &[("Resume", MenuButton::Resume), ("Quit", MenuButton::Quit)],
+    );
+}
+
+fn spawn_game_over_menu(mut commands: Commands, sim: Res<Sim>, playback: Option<Res<Playback>>) {
+    // food only ever runs out when there is nowhere left to put it
+    let title = if sim.food().is_none() { "You Win!" } else { "Game Over" };
+    let mut lines = vec![format!("Score: {}", sim.score()), format!("Seed: {}", sim.seed())];
+    if let Some(cause) = sim.snakes()[0].death() {
+        lines.insert(0, cause.describe().to_string());
+    }
+    if let Some(playback) = playback {
+        lines.push(match playback.replay.check(&sim) {
+            Ok(()) => "Replay matches the recording".to_string(),
+            Err(desync) => format!("Replay desynced, recorded score {}", desync.recorded_score),
+        });
+    }
+    spawn_menu(
+        &mut commands,
+        title,
+        &lines,
+        &[("Restart", MenuButton::Restart), ("Main Menu", MenuButton::Back), ("Quit", MenuButton::Quit)],
+    );
+}
+
+fn despawn_menu(mut commands: Commands, menus: Query<Entity, With<MenuScreen>>, mut focus: ResMut<MenuFocus>) {
+    for entity in &menus {
+        commands.entity(entity).despawn_recursive();
+    }
+    focus.0 = None;
+}
+
+// Up and Down step through the buttons on screen in reading order and wrap round at the ends
+fn move_focus(
+    actions: Res<ButtonInput<Action>>,
+    mut focus: ResMut<MenuFocus>,
+    buttons: Query<(Entity, &GlobalTransform), With<Button>>,
+) {
+    let step_down = actions.just_pressed(Action::Down);
+    if !step_down && !actions.just_pressed(Action::Up) {
+        return;
+    }
+    // ui y goes down the screen, and buttons on one row can be a fraction of a pixel apart
+    let mut order: Vec<(Entity, f32, f32)> = buttons
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation().y.round(), transform.translation().x))
+        .collect();
+    if order.is_empty() {
+        return;
+    }
+    order.sort_by(|a, b| (a.1, a.2).partial_cmp(&(b.1, b.2)).unwrap_or(Ordering::Equal));
+
+    let count = order.len();
+    let current = focus.0.and_then(|focused| order.iter().position(|(entity, _, _)| *entity == focused));
+    let next = match (current, step_down) {
+        (Some(index), true) => (index + 1) % count,
+        (Some(index), false) => (index + count - 1) % count,
+        (None, true) => 0,
+        (None, false) => count - 1,
+    };
+    focus.0 = Some(order[next].0);
+}
+
+// new buttons and ones the mouse is on or just left, or all of them when the theme or the
+// picked button changes. The picked button looks the same as one under the mouse.
+fn button_colors(
+    theme: Res<CurrentTheme>,
+    focus: Res<MenuFocus>,
+    mut interaction_query: Query<(Entity, Ref<Interaction>, &mut BackgroundColor), With<Button>>,
+) {
+    for (entity, interaction, mut color) in &mut interaction_query {
+        if !theme.is_changed() && !focus.is_changed() && !interaction.is_changed() {
+            continue;
+        }
+        *color = match *interaction {
+            Interaction::Pressed => theme.accent.into(),
+            Interaction::Hovered => theme.button_hover.into(),
+            Interaction::None if focus.0 == Some(entity) => theme.button_hover.into(),
+            Interaction::None => theme.button.into(),
+        };
+    }
+}
+
+fn menu_buttons(
+    interaction_query: Query<(Entity, Ref<Interaction>, &MenuButton)>,
+    actions: Res<ButtonInput<Action>>,
+    focus: Res<MenuFocus>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut settings: ResMut<Settings>,
+    mut settings_file: ResMut<SettingsFile>,
+    mut board_config: ResMut<BoardConfig>,
+    mut debug: ResMut<DebugMode>,
+    skins: Res<Skins>,
+    themes: Res<Assets<Theme>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (entity, interaction, action) in &interaction_query {
+        let clicked = interaction.is_changed() && *interaction == Interaction::Pressed;
+        if !clicked && !focus.confirmed(entity, &actions) {
+            continue;
+        }
+        match action {
+            MenuButton::Start | MenuButton::Resume | MenuButton::Restart => next_state.set(AppState::Playing),
+            MenuButton::HighScores => next_state.set(AppState::Leaderboard),
+            MenuButton::Options => next_state.set(AppState::Options),
+            MenuButton::Board => {
+                *board_config = board_config.next_preset();
+                let board = *board_config;
+                change_settings(&mut settings, &mut settings_file, |settings| settings.board = board);
+            }
+            MenuButton::WallMode => {
+                let wall_mode = settings.wall_mode.next();
+                change_settings(&mut settings, &mut settings_file, |settings| settings.wall_mode = wall_mode);
+            }
+            MenuButton::Difficulty => {
+                // picking a preset drops any custom curve from the config file
+                let difficulty = if settings.speed_curve.is_some() { settings.difficulty } else { settings.difficulty.next() };
+                change_settings(&mut settings, &mut settings_file, |settings| {
+                    settings.difficulty = difficulty;
+                    settings.speed_curve = None;
+                });
+            }
+            MenuButton::Skin => {
+                let skin = skins.next_after(&settings.skin);
+                change_settings(&mut settings, &mut settings_file, |settings| settings.skin = skin.clone());
+            }
+            MenuButton::Theme => {
+                let names = theme_names(&themes);
+                let index = names.iter().position(|name| *name == settings.theme).map_or(0, |index| index + 1);
+                let theme = &names[index % names.len()];
+                change_settings(&mut settings, &mut settings_file, |settings| settings.theme = theme.clone());
+            }
+            MenuButton::Debug => debug.enabled = !debug.enabled,
+            MenuButton::Controls => next_state.set(AppState::Controls),
+            MenuButton::ResetControls => {
+                change_settings(&mut settings, &mut settings_file, |settings| settings.keys = KeyBindings::default());
+            }
+            MenuButton::Back => next_state.set(AppState::MainMenu),
+            MenuButton::Quit => {
+                exit.send(AppExit);
+            }
+        }
+    }
+}
+
+// buttons for options show the value they're currently set to
+fn update_option_labels(
+    settings: Res<Settings>,
+    board_config: Res<BoardConfig>,
+    debug: Res<DebugMode>,
+    buttons: Query<(Ref<MenuButton>, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, children) in &buttons {
+        if !settings.is_changed() && !board_config.is_changed() && !debug.is_changed() && !button.is_added() {
+            continue;
+        }
+        let label = match *button {
+            MenuButton::Board => format!(
+                "Board: {}",
+                board_config.preset_name().map_or_else(
+                    || format!("{}x{}", board_config.width_cells, board_config.height_cells),
+                    str::to_string
+                )
+            ),
+            MenuButton::WallMode => format!("Walls: {}", settings.wall_mode.name()),
+            MenuButton::Difficulty => format!("Speed: {}", settings.speed_name()),
+            MenuButton::Skin => format!("Skin: {}", settings.skin),
+            MenuButton::Theme => format!("Theme: {}", settings.theme),
+            MenuButton::Debug => format!("Debug: {}", if debug.enabled { "On" } else { "Off" }),
+            _ => continue,
+        };
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+// Until a button is picked with Up and Down, Confirm does whatever the top button of a menu
+// does. Back leaves the leaderboard and options.
+fn menu_keys(
+    actions: Res<ButtonInput<Action>>,
+    focus: Res<MenuFocus>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let confirm = actions.just_pressed(Action::Confirm) && focus.0.is_none();
+    let back = actions.just_pressed(Action::Back);
+    match state.get() {
+        AppState::MainMenu | AppState::GameOver if confirm => next_state.set(AppState::Playing),
+        AppState::Leaderboard if confirm || back => next_state.set(AppState::MainMenu),
+        AppState::Options if back => next_state.set(AppState::MainMenu),
+        _ => {}
+    }
+}
+
+fn toggle_pause(
+    actions: Res<ButtonInput<Action>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    match state.get() {
+        AppState::Playing => next_state.set(AppState::Paused),
+        AppState::Paused => next_state.set(AppState::Playing),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<ButtonInput<Action>>()
+            .init_resource::<MenuFocus>()
+            .add_systems(Update, move_focus);
+        app
+    }
+
+    fn press(app: &mut App, action: Action) {
+        let mut actions = app.world.resource_mut::<ButtonInput<Action>>();
+        actions.clear();
+        actions.press(action);
+        app.update();
+        app.world.resource_mut::<ButtonInput<Action>>().release(action);
+    }
+
+    fn focused(app: &App) -> Option<Entity> {
+        app.world.resource::<MenuFocus>().0
+    }
+
+    #[test]
+    fn up_and_down_go_through_the_buttons_in_reading_order() {
+        let mut app = test_app();
+        // spawned out of order, two of them side by side on the bottom row
+        let bottom_right = app.world.spawn((Button, GlobalTransform::from_xyz(300., 200., 0.))).id();
+        let top = app.world.spawn((Button, GlobalTransform::from_xyz(200., 50., 0.))).id();
+        let bottom_left = app.world.spawn((Button, GlobalTransform::from_xyz(100., 200.2, 0.))).id();
+
+        app.update();
+        assert_eq!(focused(&app), None);
+
+        press(&mut app, Action::Down);
+        assert_eq!(focused(&app), Some(top));
+        press(&mut app, Action::Down);
+        assert_eq!(focused(&app), Some(bottom_left));
+        press(&mut app, Action::Down);
+        assert_eq!(focused(&app), Some(bottom_right));
+        press(&mut app, Action::Down);
+        assert_eq!(focused(&app), Some(top));
+        press(&mut app, Action::Up);
+        assert_eq!(focused(&app), Some(bottom_right));
+    }
+
+    #[test]
+    fn up_first_picks_the_last_button() {
+        let mut app = test_app();
+        app.world.spawn((Button, GlobalTransform::from_xyz(0., 0., 0.)));
+        let last = app.world.spawn((Button, GlobalTransform::from_xyz(0., 40., 0.))).id();
+        press(&mut app, Action::Up);
+        assert_eq!(focused(&app), Some(last));
+
+        let mut actions = ButtonInput::<Action>::default();
+        assert!(!app.world.resource::<MenuFocus>().confirmed(last, &actions));
+        actions.press(Action::Confirm);
+        assert!(app.world.resource::<MenuFocus>().confirmed(last, &actions));
+    }
+}
@@ -0,0 +1,323 @@
+// Recorded runs. The sim only depends on its seed, its config and the turns made each
+// step, so keeping those is enough to play a run again move for move.
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{Board, Difficulty, Direction, GameConfig, GameEvent, GameState, SpeedCurve, WallMode};
+use crate::storage::{self, StorageError};
+
+// bumped whenever a change to the rules would make old replays play out differently
+pub const REPLAY_VERSION: u32 = 1;
+
+// the parts of the settings that change how a run plays out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplaySettings {
+    pub width: i32,
+    pub height: i32,
+    pub wall_mode: WallMode,
+    pub lives: u32,
+    pub growth: u32,
+    pub speed_curve: SpeedCurve,
+}
+
+impl ReplaySettings {
+    pub fn new(config: &GameConfig, speed_curve: SpeedCurve) -> Self {
+        ReplaySettings {
+            width: config.board.width,
+            height: config.board.height,
+            wall_mode: config.wall_mode,
+            lives: config.lives,
+            growth: config.growth,
+            speed_curve,
+        }
+    }
+
+    // what the run was played on, as a file name. Lives and growth are left out, a best run
+    // that doesn't match them is replaced by the next one.
+    pub fn name(&self, seed: u64) -> String {
+        let speed = Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.curve() == self.speed_curve)
+            .map_or("custom", |difficulty| difficulty.name());
+        format!("{}-{}-{}x{}-{}", self.wall_mode.name(), speed, self.width, self.height, seed).to_lowercase()
+    }
+
+    pub fn game_config(&self) -> GameConfig {
+        GameConfig {
+            board: Board::new(self.width, self.height),
+            wall_mode: self.wall_mode,
+            lives: self.lives,
+            growth: self.growth,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub settings: ReplaySettings,
+    // (tick, snake, turn) for each turn made, in order. Steps where nobody turned aren't kept.
+    pub inputs: Vec<(u64, usize, Direction)>,
+    pub final_score: u32,
+    pub final_tick: u64,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Storage(StorageError),
+    Version(u32),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Storage(err) => write!(f, "{}", err),
+            ReplayError::Version(version) => {
+                write!(f, "replay is version {}, this game plays version {}", version, REPLAY_VERSION)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+// what a replay claimed at the end against what playing it again gave
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Desync {
+    pub recorded_score: u32,
+    pub recorded_tick: u64,
+    pub score: u32,
+    pub tick: u64,
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "replay desynced: recorded score {} at tick {}, played back score {} at tick {}",
+            self.recorded_score, self.recorded_tick, self.score, self.tick
+        )
+    }
+}
+
+impl std::error::Error for Desync {}
+
+impl Replay {
+    pub fn new(seed: u64, settings: ReplaySettings) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            settings,
+            inputs: Vec::new(),
+            final_score: 0,
+            final_tick: 0,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let replay: Replay = storage::load_ron_required(path).map_err(ReplayError::Storage)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        storage::save_ron_compact(self, path)
+    }
+
+    // `tick` is the sim's tick before the step the inputs were used for
+    pub fn record(&mut self, tick: u64, inputs: &[Option<Direction>]) {
+        for (snake, input) in inputs.iter().enumerate() {
+            if let Some(direction) = input {
+                self.inputs.push((tick, snake, *direction));
+            }
+        }
+    }
+
+    pub fn finish(&mut self, game: &GameState) {
+        self.final_score = game.score();
+        self.final_tick = game.tick();
+    }
+
+    // the turns to feed the step taken at `tick`
+    pub fn inputs_at(&self, tick: u64, snakes: usize) -> Vec<Option<Direction>> {
+        let start = self.inputs.partition_point(|(at, _, _)| *at < tick);
+        let mut inputs = vec![None; snakes];
+        for (_, snake, direction) in self.inputs[start..].iter().take_while(|(at, _, _)| *at == tick) {
+            if let Some(input) = inputs.get_mut(*snake) {
+                *input = Some(*direction);
+            }
+        }
+        inputs
+    }
+
+    // A fresh game for this replay, set up the same way the recorded one was
+    pub fn start(&self) -> GameState {
+        GameState::new(self.settings.game_config(), self.seed)
+    }
+
+    // Plays the replay from the start up to `tick`, or until the run ended if that's
+    // sooner. Dead snakes come back before the next step, the same as in the game.
+    pub fn seek(&self, tick: u64) -> GameState {
+        let mut game = self.start();
+        self.follow(&mut game, tick);
+        game
+    }
+
+    // more food, or the same food in fewer moves
+    pub fn beats(&self, other: &Replay) -> bool {
+        (self.final_score, std::cmp::Reverse(self.final_tick)) > (other.final_score, std::cmp::Reverse(other.final_tick))
+    }
+
+    // Steps `game` along with this replay until it has caught up with `tick`. Returns false
+    // once the recorded run is over.
+    pub fn follow(&self, game: &mut GameState, tick: u64) -> bool {
+        while game.tick() < tick.min(self.final_tick) {
+            for snake in 0..game.snakes().len() {
+                if !game.snakes()[snake].is_alive() {
+                    game.respawn(snake);
+                }
+            }
+            if !game.snakes().iter().any(|snake| snake.is_alive()) {
+                return false;
+            }
+            let inputs = self.inputs_at(game.tick(), game.snakes().len());
+            if game.step(&inputs).contains(&GameEvent::BoardFull) {
+                return false;
+            }
+        }
+        game.tick() < self.final_tick
+    }
+
+    // the played back game should end exactly where the recorded one did
+    pub fn check(&self, game: &GameState) -> Result<(), Desync> {
+        if game.score() == self.final_score && game.tick() == self.final_tick {
+            return Ok(());
+        }
+        Err(Desync {
+            recorded_score: self.final_score,
+            recorded_tick: self.final_tick,
+            score: game.score(),
+            tick: game.tick(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::Cell;
+
+    fn settings() -> ReplaySettings {
+        let config = GameConfig { board: Board::new(12, 10), lives: 3, ..GameConfig::default() };
+        ReplaySettings::new(&config, SpeedCurve::Linear { start_ms: 100, step_ms: 2, min_ms: 50 })
+    }
+
+    // heads for the food without running into anything it can see, turning as little as it can
+    fn pick_turn(game: &GameState) -> Option<Direction> {
+        let snake = &game.snakes()[0];
+        let food = game.food()?;
+        let distance = |cell: Cell| (cell.x - food.x).abs() + (cell.y - food.y).abs();
+        let direction = Direction::ALL
+            .into_iter()
+            .filter(|direction| *direction != snake.direction().opposite())
+            .map(|direction| (direction, snake.head().step(direction)))
+            .filter(|(_, cell)| game.board().contains(*cell) && !snake.body().contains(cell))
+            .min_by_key(|(direction, cell)| (distance(*cell), *direction != snake.direction()))?
+            .0;
+        Some(direction).filter(|direction| *direction != snake.direction())
+    }
+
+    // plays a run and records it the way the game does
+    fn record_run(seed: u64, max_ticks: u64) -> (Replay, GameState) {
+        let mut replay = Replay::new(seed, settings());
+        let mut game = replay.start();
+        loop {
+            if !game.snakes()[0].is_alive() && game.respawn(0).is_none() {
+                break;
+            }
+            if game.tick() >= max_ticks {
+                break;
+            }
+            let inputs = [pick_turn(&game)];
+            replay.record(game.tick(), &inputs);
+            if game.step(&inputs).contains(&GameEvent::BoardFull) {
+                break;
+            }
+        }
+        replay.finish(&game);
+        (replay, game)
+    }
+
+    #[test]
+    fn inputs_at_picks_out_one_step() {
+        let mut replay = Replay::new(1, settings());
+        replay.record(3, &[Some(Direction::Left), None]);
+        replay.record(5, &[None, Some(Direction::Down)]);
+        replay.record(6, &[Some(Direction::Up), Some(Direction::Right)]);
+
+        assert_eq!(replay.inputs_at(0, 2), [None, None]);
+        assert_eq!(replay.inputs_at(3, 2), [Some(Direction::Left), None]);
+        assert_eq!(replay.inputs_at(4, 2), [None, None]);
+        assert_eq!(replay.inputs_at(5, 2), [None, Some(Direction::Down)]);
+        assert_eq!(replay.inputs_at(6, 2), [Some(Direction::Up), Some(Direction::Right)]);
+        // a snake that isn't in the game is left out
+        assert_eq!(replay.inputs_at(6, 1), [Some(Direction::Up)]);
+        assert_eq!(replay.inputs_at(7, 2), [None, None]);
+    }
+
+    #[test]
+    fn playing_the_inputs_again_ends_the_same() {
+        let (replay, recorded) = record_run(7, 2000);
+        assert!(!replay.inputs.is_empty());
+        assert!(recorded.score() > 0);
+
+        let played = replay.seek(replay.final_tick);
+        assert_eq!(replay.check(&played), Ok(()));
+        assert_eq!(played.score(), recorded.score());
+        assert_eq!(played.tick(), recorded.tick());
+        assert_eq!(played.food(), recorded.food());
+        assert_eq!(played.snakes()[0].body(), recorded.snakes()[0].body());
+        assert_eq!(played.snakes()[0].lives(), recorded.snakes()[0].lives());
+    }
+
+    #[test]
+    fn a_saved_replay_plays_back_the_same() {
+        let (replay, _) = record_run(11, 500);
+        let loaded: Replay = ron::from_str(&ron::to_string(&replay).unwrap()).unwrap();
+        assert_eq!(loaded, replay);
+        assert_eq!(loaded.check(&loaded.seek(loaded.final_tick)), Ok(()));
+    }
+
+    #[test]
+    fn other_inputs_desync() {
+        let (mut replay, _) = record_run(7, 2000);
+        replay.inputs.clear();
+        assert!(replay.check(&replay.seek(replay.final_tick)).is_err());
+    }
+
+    #[test]
+    fn seeking_partway_matches_following_along() {
+        let (replay, _) = record_run(3, 300);
+        let halfway = replay.final_tick / 2;
+        let mut game = replay.start();
+        assert!(replay.follow(&mut game, halfway));
+        let sought = replay.seek(halfway);
+        assert_eq!(game.tick(), halfway);
+        assert_eq!(sought.tick(), halfway);
+        assert_eq!(sought.score(), game.score());
+        assert_eq!(sought.snakes()[0].body(), game.snakes()[0].body());
+    }
+
+    #[test]
+    fn seeking_past_the_end_stops_where_the_run_did() {
+        let (replay, recorded) = record_run(7, 2000);
+        let played = replay.seek(u64::MAX);
+        assert_eq!(played.tick(), recorded.tick());
+        assert_eq!(replay.check(&played), Ok(()));
+    }
+}
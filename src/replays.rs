@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use snake_game::replay::{Replay, ReplaySettings};
+
+use crate::board::BoardConfig;
+use crate::cli::Args;
+use crate::controls::Action;
+use crate::debug::DebugMode;
+use crate::ghost::{keep_if_best, GhostRun};
+use crate::leaderboard::HighScores;
+use crate::settings::Settings;
+use crate::snake::{DeathAnimation, PlayerInput, RunStarted, Sim, SnakeSegment, SpeedProgression};
+use crate::theme::ThemeColor;
+use crate::AppState;
+
+// Records every run to a replay file, and plays one back for --replay
+pub struct ReplaysPlugin;
+
+impl Plugin for ReplaysPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (start_playback, spawn_playback_overlay).run_if(resource_exists::<Playback>))
+            .add_systems(Update, start_recording.run_if(not(resource_exists::<Playback>)))
+            .add_systems(OnEnter(AppState::GameOver), (save_recording, check_playback.run_if(resource_exists::<Playback>)))
+            .add_systems(OnEnter(AppState::EnterName), save_recording)
+            .add_systems(
+                Update,
+                (
+                    playback_keys.run_if(in_state(AppState::Playing)),
+                    update_playback_overlay,
+                )
+                    .run_if(resource_exists::<Playback>),
+            );
+    }
+}
+
+// how far Left and Right jump through a replay
+const SEEK_TICKS: u64 = 50;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 16.;
+
+// the run being played, saved once it's over
+#[derive(Resource)]
+pub struct Recording(pub Replay);
+
+// The replay being watched. The sim takes its turns from here instead of from the players.
+#[derive(Resource)]
+pub struct Playback {
+    pub replay: Replay,
+    // how many times faster than it was played
+    pub speed: f32,
+}
+
+#[derive(Component)]
+struct PlaybackText;
+
+// Reads the file given with --replay, or --ghost to race it, and sets the game up the way
+// that run was played. `settings` is what the game plays by, config.ron isn't touched.
+pub fn load_replay_args(args: &mut Args, settings: &mut Settings) -> (Option<Playback>, Option<GhostRun>) {
+    let base = settings.board;
+    let load = |path: &Path| {
+        let loaded = Replay::load(path).map_err(|err| err.to_string()).and_then(|replay| {
+            // a broken or hand edited file could ask for any size of board
+            let size = format!("{}x{}", replay.settings.width, replay.settings.height);
+            BoardConfig::parse(&size, base).map(|_| replay).map_err(|err| err.to_string())
+        });
+        match loaded {
+            Ok(replay) => replay,
+            Err(err) => {
+                eprintln!("Can't play {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        }
+    };
+    let playback = args.replay.as_deref().map(load);
+    let ghost = args.ghost.as_deref().map(load);
+    if let Some(replay) = playback.as_ref().or(ghost.as_ref()) {
+        settings.board.width_cells = replay.settings.width;
+        settings.board.height_cells = replay.settings.height;
+        settings.wall_mode = replay.settings.wall_mode;
+        settings.lives = replay.settings.lives;
+        settings.growth = replay.settings.growth;
+        settings.speed_curve = Some(replay.settings.speed_curve.clone());
+        args.seed = Some(replay.seed);
+    }
+    (playback.map(|replay| Playback { replay, speed: 1. }), ghost.map(GhostRun))
+}
+
+fn start_playback(mut next_state: ResMut<NextState<AppState>>) {
+    next_state.set(AppState::Playing);
+}
+
+// debug runs aren't recorded, the cheats would make them play out differently
+fn start_recording(
+    mut commands: Commands,
+    mut run_started: EventReader<RunStarted>,
+    sim: Res<Sim>,
+    speed: Res<SpeedProgression>,
+    debug: Res<DebugMode>,
+) {
+    if run_started.read().count() == 0 {
+        return;
+    }
+    if debug.enabled {
+        commands.remove_resource::<Recording>();
+        return;
+    }
+    let settings = ReplaySettings::new(sim.config(), (**speed).clone());
+    commands.insert_resource(Recording(Replay::new(sim.seed(), settings)));
+}
+
+fn save_recording(
+    mut commands: Commands,
+    recording: Option<ResMut<Recording>>,
+    sim: Res<Sim>,
+    high_scores: Res<HighScores>,
+    player_input: Res<PlayerInput>,
+) {
+    let Some(mut recording) = recording else { return };
+    commands.remove_resource::<Recording>();
+    recording.0.finish(&sim);
+    if !player_input.ai_playing() {
+        keep_if_best(&recording.0, &high_scores);
+    }
+    let Some(dir) = high_scores.replay_dir() else { return };
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let path: PathBuf = dir.join(format!("{}-{}.ron", started, sim.seed()));
+    match recording.0.save(&path) {
+        Ok(()) => eprintln!("Replay saved to {}", path.display()),
+        Err(err) => error!("Replay: {}", err),
+    }
+}
+
+fn check_playback(playback: Res<Playback>, sim: Res<Sim>) {
+    match playback.replay.check(&sim) {
+        Ok(()) => eprintln!("Replay finished with score {}, matches the recording", sim.score()),
+        Err(desync) => eprintln!("{}", desync),
+    }
+}
+
+// Up and Down change the speed, Left and Right jump back and forward. Pausing is the
+// game's own pause.
+fn playback_keys(
+    mut commands: Commands,
+    actions: Res<ButtonInput<Action>>,
+    mut playback: ResMut<Playback>,
+    mut sim: ResMut<Sim>,
+    mut segments: Query<&mut Visibility, With<SnakeSegment>>,
+    mut run_started: EventWriter<RunStarted>,
+) {
+    if actions.just_pressed(Action::Up) {
+        playback.speed = (playback.speed * 2.).min(MAX_SPEED);
+    }
+    if actions.just_pressed(Action::Down) {
+        playback.speed = (playback.speed / 2.).max(MIN_SPEED);
+    }
+    let target = if actions.just_pressed(Action::Right) {
+        (sim.tick() + SEEK_TICKS).min(playback.replay.final_tick)
+    } else if actions.just_pressed(Action::Left) {
+        sim.tick().saturating_sub(SEEK_TICKS)
+    } else {
+        return;
+    };
+
+    **sim = playback.replay.seek(target);
+    // a blink might have been cut off half way
+    commands.remove_resource::<DeathAnimation>();
+    for mut visibility in &mut segments {
+        *visibility = Visibility::Inherited;
+    }
+    if let Some(snake) = sim.snakes().iter().position(|snake| !snake.is_alive()) {
+        commands.insert_resource(DeathAnimation::start(snake));
+    }
+    // the board has been swapped out, same as at the start of a run
+    run_started.send(RunStarted);
+}
+
+fn spawn_playback_overlay(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(4.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        PlaybackText,
+        ThemeColor::Accent,
+    ));
+}
+
+fn update_playback_overlay(playback: Res<Playback>, sim: Res<Sim>, mut text: Query<&mut Text, With<PlaybackText>>) {
+    let line = format!(
+        "Replay x{} | tick {}/{} | Up/Down speed, Left/Right seek, P pause",
+        playback.speed,
+        sim.tick(),
+        playback.replay.final_tick
+    );
+    for mut text in &mut text {
+        if text.sections[0].value != line {
+            text.sections[0].value = line.clone();
+        }
+    }
+}
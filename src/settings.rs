@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use snake_game::ai::{Pilot, Strategy};
+use snake_game::sim::{Difficulty, SpeedCurve, WallMode, DEFAULT_TURN_BUFFER};
+use snake_game::storage::{self, StorageError};
+
+use crate::board::BoardConfig;
+use crate::cli::Args;
+use crate::controls::{GamepadBindings, KeyBindings};
+
+// Options that stick around between runs, saved to config.ron in the user's config directory.
+// Anything missing from the file keeps its default.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub title: String,
+    // starting window size in pixels, None fits the window to the board
+    pub window_size: Option<(f32, f32)>,
+    pub fullscreen: bool,
+    pub board: BoardConfig,
+    pub wall_mode: WallMode,
+    pub lives: u32,
+    // how many segments each piece of food adds
+    pub growth: u32,
+    // plays every run on this seed, --seed wins over it
+    pub seed: Option<u64>,
+    pub difficulty: Difficulty,
+    // a hand written curve to use instead of the difficulty's
+    pub speed_curve: Option<SpeedCurve>,
+    // how many turns can be queued up ahead of the snake
+    pub turn_buffer: usize,
+    // who steers the snake, the player or one of the AI strategies
+    pub pilot: Pilot,
+    // the strategy the debug autopilot flies the player's snake with
+    pub autopilot: Strategy,
+    // folder name under assets/skins, or "flat" for plain squares
+    pub skin: String,
+    // name of a theme in assets/themes
+    pub theme: String,
+    pub keys: KeyBindings,
+    pub buttons: GamepadBindings,
+    // how far the left stick has to be pushed before it turns the snake, 0 to 1
+    pub stick_deadzone: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            title: "Snake".to_string(),
+            window_size: None,
+            fullscreen: false,
+            board: BoardConfig::default(),
+            wall_mode: WallMode::default(),
+            lives: 1,
+            growth: 1,
+            seed: None,
+            difficulty: Difficulty::default(),
+            speed_curve: None,
+            turn_buffer: DEFAULT_TURN_BUFFER,
+            pilot: Pilot::Human,
+            autopilot: Strategy::default(),
+            skin: "classic".to_string(),
+            theme: "Classic".to_string(),
+            keys: KeyBindings::default(),
+            buttons: GamepadBindings::default(),
+            stick_deadzone: 0.5,
+        }
+    }
+}
+
+// what --print-default-config writes above each setting
+const NOTES: [(&str, &str); 18] = [
+    ("title", "window title"),
+    ("window_size", "starting window size in pixels, e.g. Some((1280, 960)), None fits the window to the board"),
+    ("fullscreen", "start fullscreen, F11 switches while playing"),
+    ("board", "size of the board in cells and how many pixels a cell is, 5 to 256 cells a side"),
+    ("wall_mode", "Solid, Wrap or Clamp"),
+    ("lives", "how many times the snake can die before the run ends"),
+    ("growth", "how many segments each piece of food adds"),
+    ("seed", "play every run on the same board, e.g. Some(1234), None draws a new seed for each run"),
+    ("difficulty", "Easy, Normal, Hard or Insane"),
+    ("speed_curve", "replaces the difficulty's speeds, e.g. Some(Linear(start_ms: 120, step_ms: 2, min_ms: 40))"),
+    ("turn_buffer", "how many turns can be queued up ahead of the snake"),
+    ("pilot", "who steers the snake, Human or Ai(Greedy), Ai(AStar) or Ai(Hamiltonian)"),
+    ("autopilot", "Greedy, AStar or Hamiltonian, flies the player's snake when O is pressed in debug mode"),
+    ("skin", "folder name under assets/skins, or \"flat\" for plain squares"),
+    ("theme", "name of a theme in assets/themes"),
+    ("keys", "keys for each action, at most two each"),
+    ("buttons", "gamepad buttons for each action"),
+    ("stick_deadzone", "how far the left stick has to be pushed before it turns the snake, 0 to 1"),
+];
+
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("snake_game").join("config.ron"))
+}
+
+// What's in config.ron, kept apart from the Settings resource the game plays by. Flags and
+// replays only ever change the latter, so saving doesn't write them into the file.
+#[derive(Resource, Debug, Clone)]
+pub struct SettingsFile(pub Settings);
+
+// An option changed from inside the game, it goes into the game in progress and into
+// config.ron alike. A file that can't be written is only logged.
+pub fn change_settings(settings: &mut Settings, file: &mut SettingsFile, change: impl Fn(&mut Settings)) {
+    change(settings);
+    change(&mut file.0);
+    if let Err(err) = file.0.save() {
+        error!("Settings: {}", err);
+    }
+}
+
+impl Settings {
+    // a broken config shouldn't stop the game from starting, so fall back to the defaults
+    pub fn load_or_default() -> Self {
+        let Some(path) = default_path() else { return Settings::default() };
+        warn_unknown_keys(&path);
+        match storage::load_ron(&path) {
+            Ok(settings) => settings,
+            Err(err) => {
+                // the next save would write over the broken file, keep a copy to fix by hand
+                if let StorageError::Parse(..) = err {
+                    let backup = path.with_extension("ron.bak");
+                    if std::fs::copy(&path, &backup).is_ok() {
+                        eprintln!("Copied unreadable settings to {}", backup.display());
+                    }
+                }
+                eprintln!("Using default settings, {}", err);
+                Settings::default()
+            }
+        }
+    }
+
+    // Command line options win over the file. A value that doesn't make sense is reported
+    // and the file's value kept.
+    pub fn apply_args(&mut self, args: &Args) {
+        if let Err(err) = self.board.validate() {
+            eprintln!("Using the default board, {}", err);
+            self.board = BoardConfig::default();
+        }
+        if let Some(board) = &args.board {
+            match BoardConfig::parse(board, self.board) {
+                Ok(board) => self.board = board,
+                Err(err) => eprintln!("Ignoring --board, {}", err),
+            }
+        }
+        if let Some(speed) = &args.speed {
+            match (Difficulty::ALL.into_iter().find(|d| d.name().eq_ignore_ascii_case(speed)), speed.parse::<u64>()) {
+                (Some(difficulty), _) => {
+                    self.difficulty = difficulty;
+                    self.speed_curve = None;
+                }
+                // a plain number is a fixed move time in milliseconds
+                (None, Ok(ms)) => self.speed_curve = Some(SpeedCurve::Linear { start_ms: ms, step_ms: 0, min_ms: ms }),
+                (None, Err(_)) => eprintln!("Ignoring --speed {}, use easy, normal, hard, insane or milliseconds", speed),
+            }
+        }
+        if let Some(mode) = &args.mode {
+            match WallMode::ALL.into_iter().find(|m| m.name().eq_ignore_ascii_case(mode)) {
+                Some(wall_mode) => self.wall_mode = wall_mode,
+                None => eprintln!("Ignoring --mode {}, use solid, wrap or clamp", mode),
+            }
+        }
+        if let Some(ai) = &args.ai {
+            match Strategy::parse(ai) {
+                Some(strategy) => self.pilot = Pilot::Ai(strategy),
+                None => eprintln!("Ignoring --ai {}, use greedy, astar or hamiltonian", ai),
+            }
+        }
+    }
+
+    pub fn speed_curve(&self) -> SpeedCurve {
+        self.speed_curve.clone().unwrap_or_else(|| self.difficulty.curve())
+    }
+
+    pub fn speed_name(&self) -> &'static str {
+        match self.speed_curve {
+            Some(_) => "Custom",
+            None => self.difficulty.name(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), StorageError> {
+        match default_path() {
+            Some(path) => storage::save_ron(self, &path),
+            None => Ok(()),
+        }
+    }
+
+    // the default config.ron with a comment on each setting
+    pub fn default_file() -> String {
+        let pretty = ron::ser::PrettyConfig::default();
+        let text = ron::ser::to_string_pretty(&Settings::default(), pretty).unwrap_or_default();
+        let mut file = String::from("// snake_game settings, anything left out keeps its default\n");
+        for line in text.lines() {
+            let note = line
+                .strip_prefix("    ")
+                .filter(|rest| !rest.starts_with(' '))
+                .and_then(|rest| NOTES.iter().find(|(key, _)| rest.starts_with(&format!("{}:", key))))
+                .map(|(_, note)| *note);
+            if let Some(note) = note {
+                file.push_str(&format!("    // {}\n", note));
+            }
+            file.push_str(line);
+            file.push('\n');
+        }
+        file
+    }
+}
+
+// Settings ignores keys it doesn't know, which hides typos, so say which ones were skipped
+fn warn_unknown_keys(path: &Path) {
+    let Ok(text) = std::fs::read_to_string(path) else { return };
+    let Ok(file) = ron::from_str::<ron::Value>(&text) else { return };
+    let Ok(known) = ron::to_string(&Settings::default()).and_then(|text| Ok(ron::from_str::<ron::Value>(&text)?)) else {
+        return;
+    };
+    for key in unknown_keys(&file, &known, "") {
+        eprintln!("Unknown setting {} in {}, it will be ignored", key, path.display());
+    }
+}
+
+// only looks inside structs, maps like the key bindings can hold anything
+fn unknown_keys(file: &ron::Value, known: &ron::Value, prefix: &str) -> Vec<String> {
+    let (ron::Value::Map(file), ron::Value::Map(known)) = (file, known) else { return Vec::new() };
+    let mut unknown = Vec::new();
+    for (key, value) in file.iter() {
+        let ron::Value::String(name) = key else { continue };
+        match known.iter().find(|(known_key, _)| *known_key == key) {
+            Some((_, known)) => unknown.extend(unknown_keys(value, known, &format!("{}{}.", prefix, name))),
+            None => unknown.push(format!("{}{}", prefix, name)),
+        }
+    }
+    unknown
+}
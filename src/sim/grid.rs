@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    // how far one step in this direction moves on the grid, y goes up like bevy's world space
+    pub fn offset(self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+// A single square on the board, (0, 0) is the bottom left corner
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, Default)]
+pub struct Cell {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Cell {
+    pub const fn new(x: i32, y: i32) -> Self {
+        Cell { x, y }
+    }
+
+    pub fn step(self, direction: Direction) -> Cell {
+        let (dx, dy) = direction.offset();
+        Cell::new(self.x + dx, self.y + dy)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Board {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Board {
+    // the snake needs some room to turn around in
+    pub const MIN_SIDE: i32 = 5;
+    // past this there are more segments than is sensible to draw
+    pub const MAX_SIDE: i32 = 256;
+
+    pub const fn new(width: i32, height: i32) -> Self {
+        Board { width, height }
+    }
+
+    pub fn contains(&self, cell: Cell) -> bool {
+        cell.x >= 0 && cell.x < self.width && cell.y >= 0 && cell.y < self.height
+    }
+
+    pub fn clamp(&self, cell: Cell) -> Cell {
+        Cell::new(cell.x.clamp(0, self.width - 1), cell.y.clamp(0, self.height - 1))
+    }
+
+    // off one edge comes back on the opposite one
+    pub fn wrap(&self, cell: Cell) -> Cell {
+        Cell::new(cell.x.rem_euclid(self.width), cell.y.rem_euclid(self.height))
+    }
+
+    pub fn center(&self) -> Cell {
+        Cell::new(self.width / 2, self.height / 2)
+    }
+
+    pub fn cell_count(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+}
+
+impl Default for Board {
+    // same play area the 640x480 window with 10px squares used to give
+    fn default() -> Self {
+        Board::new(64, 48)
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use super::grid::Direction;
+
+pub const DEFAULT_TURN_BUFFER: usize = 3;
+
+// Turns pressed faster than the snake moves wait in here and get used up one per step.
+// Each turn is checked against the one before it, or against the direction the snake
+// actually moved last if nothing is queued, so two quick presses can't add up to a 180.
+#[derive(Debug, Clone)]
+pub struct TurnBuffer {
+    turns: VecDeque<Direction>,
+    capacity: usize,
+}
+
+impl Default for TurnBuffer {
+    fn default() -> Self {
+        TurnBuffer::new(DEFAULT_TURN_BUFFER)
+    }
+}
+
+impl TurnBuffer {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        TurnBuffer {
+            turns: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn turns(&self) -> impl Iterator<Item = Direction> + '_ {
+        self.turns.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    // queue a turn, returns false if it was dropped because the buffer is full or the
+    // turn wouldn't change anything (same way, or straight back)
+    pub fn push(&mut self, direction: Direction, moved: Direction) -> bool {
+        let last = self.turns.back().copied().unwrap_or(moved);
+        if direction == last || direction == last.opposite() || self.turns.len() >= self.capacity {
+            return false;
+        }
+        self.turns.push_back(direction);
+        true
+    }
+
+    // the turn for the next step. Anything that became a reversal since it was queued,
+    // e.g. after a respawn, is skipped
+    pub fn pop(&mut self, moved: Direction) -> Option<Direction> {
+        while let Some(direction) = self.turns.pop_front() {
+            if direction != moved && direction != moved.opposite() {
+                return Some(direction);
+            }
+        }
+        None
+    }
+
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queues_up_to_its_capacity() {
+        let mut buffer = TurnBuffer::new(2);
+        assert!(buffer.push(Direction::Left, Direction::Up));
+        assert!(buffer.push(Direction::Up, Direction::Up));
+        assert!(!buffer.push(Direction::Right, Direction::Up));
+        assert_eq!(buffer.turns().collect::<Vec<_>>(), [Direction::Left, Direction::Up]);
+        // there's always room for at least one
+        assert_eq!(TurnBuffer::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn drops_turns_that_change_nothing() {
+        let mut buffer = TurnBuffer::new(3);
+        assert!(!buffer.push(Direction::Up, Direction::Up));
+        assert!(!buffer.push(Direction::Down, Direction::Up));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn checks_against_the_last_queued_turn() {
+        // left then right would be a 180 in two quick presses
+        let mut buffer = TurnBuffer::new(3);
+        assert!(buffer.push(Direction::Left, Direction::Up));
+        assert!(!buffer.push(Direction::Right, Direction::Up));
+        assert!(buffer.push(Direction::Down, Direction::Up));
+        assert_eq!(buffer.turns().collect::<Vec<_>>(), [Direction::Left, Direction::Down]);
+    }
+
+    #[test]
+    fn pops_in_order_and_skips_stale_reversals() {
+        let mut buffer = TurnBuffer::new(3);
+        buffer.push(Direction::Left, Direction::Up);
+        buffer.push(Direction::Down, Direction::Up);
+        assert_eq!(buffer.pop(Direction::Up), Some(Direction::Left));
+        // respawned facing up, so the queued down is a reversal now
+        assert_eq!(buffer.pop(Direction::Up), None);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,11 @@
+// Pure snake simulation. Nothing in here knows about bevy, entities or transforms,
+// the bevy systems in main.rs just step a GameState and mirror it into entities.
+mod grid;
+mod input;
+mod speed;
+mod state;
+
+pub use grid::{Board, Cell, Direction};
+pub use input::{TurnBuffer, DEFAULT_TURN_BUFFER};
+pub use speed::{Difficulty, SpeedCurve};
+pub use state::{DeathCause, GameConfig, GameEvent, GameState, Snake, WallMode};
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+// How long a move takes for a given score, the snake speeds up as it eats
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpeedCurve {
+    // every point of score takes step_ms off
+    Linear { start_ms: u64, step_ms: u64, min_ms: u64 },
+    // step_ms comes off once every `every` points
+    Stepped { start_ms: u64, every: u32, step_ms: u64, min_ms: u64 },
+    // (score, interval_ms) pairs, the last one the score has reached is used
+    Table(Vec<(u32, u64)>),
+}
+
+// nothing should ever move faster than this, whatever a custom curve says
+const FASTEST_MS: u64 = 10;
+
+impl SpeedCurve {
+    pub fn interval(&self, score: u32) -> Duration {
+        let ms = match self {
+            SpeedCurve::Linear { start_ms, step_ms, min_ms } => {
+                start_ms.saturating_sub(step_ms.saturating_mul(score as u64)).max(*min_ms)
+            }
+            SpeedCurve::Stepped { start_ms, every, step_ms, min_ms } => {
+                let steps = (score / (*every).max(1)) as u64;
+                start_ms.saturating_sub(step_ms.saturating_mul(steps)).max(*min_ms)
+            }
+            SpeedCurve::Table(table) => table
+                .iter()
+                .filter(|(from, _)| *from <= score)
+                .max_by_key(|(from, _)| *from)
+                .or_else(|| table.iter().min_by_key(|(from, _)| *from))
+                .map_or(100, |(_, ms)| *ms),
+        };
+        Duration::from_millis(ms.max(FASTEST_MS))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Insane,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Insane];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Insane => "Insane",
+        }
+    }
+
+    pub fn next(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Insane,
+            Difficulty::Insane => Difficulty::Easy,
+        }
+    }
+
+    pub fn curve(self) -> SpeedCurve {
+        match self {
+            Difficulty::Easy => SpeedCurve::Linear { start_ms: 150, step_ms: 1, min_ms: 90 },
+            Difficulty::Normal => SpeedCurve::Linear { start_ms: 100, step_ms: 2, min_ms: 50 },
+            Difficulty::Hard => SpeedCurve::Stepped { start_ms: 80, every: 5, step_ms: 5, min_ms: 40 },
+            Difficulty::Insane => SpeedCurve::Table(vec![(0, 60), (10, 50), (25, 40), (50, 30)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(curve: &SpeedCurve, score: u32) -> u64 {
+        curve.interval(score).as_millis() as u64
+    }
+
+    #[test]
+    fn linear_speeds_up_every_point_down_to_its_minimum() {
+        let curve = SpeedCurve::Linear { start_ms: 100, step_ms: 2, min_ms: 50 };
+        assert_eq!(ms(&curve, 0), 100);
+        assert_eq!(ms(&curve, 10), 80);
+        assert_eq!(ms(&curve, 25), 50);
+        assert_eq!(ms(&curve, 1000), 50);
+    }
+
+    #[test]
+    fn stepped_speeds_up_every_few_points() {
+        let curve = SpeedCurve::Stepped { start_ms: 80, every: 5, step_ms: 5, min_ms: 40 };
+        assert_eq!(ms(&curve, 4), 80);
+        assert_eq!(ms(&curve, 5), 75);
+        assert_eq!(ms(&curve, 9), 75);
+        assert_eq!(ms(&curve, 10), 70);
+        assert_eq!(ms(&curve, 100), 40);
+        // every: 0 is treated as every point rather than dividing by zero
+        let curve = SpeedCurve::Stepped { start_ms: 80, every: 0, step_ms: 5, min_ms: 40 };
+        assert_eq!(ms(&curve, 2), 70);
+    }
+
+    #[test]
+    fn table_uses_the_last_row_reached() {
+        let curve = SpeedCurve::Table(vec![(20, 70), (0, 100), (50, 40)]);
+        assert_eq!(ms(&curve, 0), 100);
+        assert_eq!(ms(&curve, 19), 100);
+        assert_eq!(ms(&curve, 20), 70);
+        assert_eq!(ms(&curve, 99), 40);
+        // below the first row the first row still counts
+        assert_eq!(ms(&SpeedCurve::Table(vec![(10, 90)]), 0), 90);
+        assert_eq!(ms(&SpeedCurve::Table(Vec::new()), 0), 100);
+    }
+
+    #[test]
+    fn nothing_moves_faster_than_the_floor() {
+        let curve = SpeedCurve::Linear { start_ms: 5, step_ms: 0, min_ms: 0 };
+        assert_eq!(ms(&curve, 0), FASTEST_MS);
+    }
+
+    #[test]
+    fn harder_difficulties_are_never_slower() {
+        for score in [0, 10, 30, 100] {
+            let intervals: Vec<u64> = Difficulty::ALL.iter().map(|difficulty| ms(&difficulty.curve(), score)).collect();
+            assert!(intervals.windows(2).all(|pair| pair[0] >= pair[1]), "score {}: {:?}", score, intervals);
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use super::grid::{Board, Cell, Direction};
+
+// What happens when a snake runs off the edge of the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum WallMode {
+    #[default]
+    Solid,  // the snake dies
+    Wrap,   // it comes out the opposite side
+    Clamp,  // it stays pressed against the wall, only really useful for debugging
+}
+
+impl WallMode {
+    pub const ALL: [WallMode; 3] = [WallMode::Solid, WallMode::Wrap, WallMode::Clamp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WallMode::Solid => "Solid",
+            WallMode::Wrap => "Wrap",
+            WallMode::Clamp => "Clamp",
+        }
+    }
+
+    pub fn next(self) -> WallMode {
+        match self {
+            WallMode::Solid => WallMode::Wrap,
+            WallMode::Wrap => WallMode::Clamp,
+            WallMode::Clamp => WallMode::Solid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameConfig {
+    pub board: Board,
+    pub wall_mode: WallMode,
+    pub lives: u32,     // how many times each snake can die before it's out
+    pub growth: u32,    // segments added for each piece of food
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            board: Board::default(),
+            wall_mode: WallMode::default(),
+            lives: 1,
+            growth: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeathCause {
+    Wall,
+    OwnBody,
+}
+
+impl DeathCause {
+    pub fn describe(self) -> &'static str {
+        match self {
+            DeathCause::Wall => "Ran into the wall",
+            DeathCause::OwnBody => "Bit its own tail",
+        }
+    }
+}
+
+// Things that happened during a step, the bevy side reacts to these instead of diffing state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    FoodEaten { snake: usize, cell: Cell },
+    FoodSpawned { cell: Cell },
+    Grew { snake: usize },
+    SnakeDied { snake: usize, cause: DeathCause },
+    Respawned { snake: usize },
+    // an invincible snake ran into itself and lost everything behind its head
+    TailReset { snake: usize },
+    // the last segment came off outside of a step, only the debug tools do this
+    Shrank { snake: usize },
+    // there was nowhere left to put food, which means the board is covered
+    BoardFull,
+}
+
+#[derive(Debug, Clone)]
+pub struct Snake {
+    body: VecDeque<Cell>,   // front is the head
+    direction: Direction,
+    pending_growth: u32,
+    death: Option<DeathCause>,
+    lives: u32,     // including the one being played
+    invincible: bool,
+}
+
+impl Snake {
+    fn new(head: Cell, direction: Direction, lives: u32) -> Self {
+        Snake {
+            body: VecDeque::from([head]),
+            direction,
+            pending_growth: 0,
+            death: None,
+            lives,
+            invincible: false,
+        }
+    }
+
+    // Invincible snakes play by the old sandbox rules, walls hold them in and biting
+    // themselves only costs the tail
+    pub fn is_invincible(&self) -> bool {
+        self.invincible
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.death.is_none()
+    }
+
+    // what killed the snake, None while it's still going
+    pub fn death(&self) -> Option<DeathCause> {
+        self.death
+    }
+
+    pub fn lives(&self) -> u32 {
+        self.lives
+    }
+
+    fn die(&mut self, cause: DeathCause) {
+        self.death = Some(cause);
+        self.lives = self.lives.saturating_sub(1);
+    }
+
+    pub fn head(&self) -> Cell {
+        self.body[0]
+    }
+
+    pub fn body(&self) -> &VecDeque<Cell> {
+        &self.body
+    }
+
+    pub fn length(&self) -> usize {
+        self.body.len()
+    }
+
+    // segments still to be added from food already eaten, one per step
+    pub fn pending_growth(&self) -> u32 {
+        self.pending_growth
+    }
+
+    // direction the snake moved on its last step
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    fn turn(&mut self, direction: Direction) {
+        if direction != self.direction.opposite() {
+            self.direction = direction;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameState {
+    config: GameConfig,
+    snakes: Vec<Snake>,
+    food: Option<Cell>,
+    obstacles: HashSet<Cell>,
+    score: u32,
+    tick: u64,
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameState {
+    pub fn new(config: GameConfig, seed: u64) -> Self {
+        let mut state = GameState {
+            config,
+            snakes: vec![Snake::new(config.board.center(), Direction::Up, config.lives.max(1))],
+            food: None,
+            obstacles: HashSet::new(),
+            score: 0,
+            tick: 0,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        };
+        state.spawn_food();
+        state
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    pub fn board(&self) -> Board {
+        self.config.board
+    }
+
+    pub fn snakes(&self) -> &[Snake] {
+        &self.snakes
+    }
+
+    pub fn food(&self) -> Option<Cell> {
+        self.food
+    }
+
+    pub fn obstacles(&self) -> &HashSet<Cell> {
+        &self.obstacles
+    }
+
+    // Blocks a cell for food. Does nothing if something is already there.
+    pub fn add_obstacle(&mut self, cell: Cell) {
+        if self.config.board.contains(cell) && self.is_free(cell) {
+            self.obstacles.insert(cell);
+        }
+    }
+
+    pub fn is_free(&self, cell: Cell) -> bool {
+        self.food != Some(cell)
+            && !self.obstacles.contains(&cell)
+            && !self.snakes.iter().any(|snake| snake.body.contains(&cell))
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    // the same seed and inputs always play out the same game
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Advance the game by one move. inputs[i] is the turn requested for snake i this step,
+    // a turn straight back into the snake is ignored.
+    pub fn step(&mut self, inputs: &[Option<Direction>]) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        self.tick += 1;
+
+        let board = self.config.board;
+        for (index, snake) in self.snakes.iter_mut().enumerate() {
+            if !snake.is_alive() {
+                continue;
+            }
+            if let Some(Some(direction)) = inputs.get(index) {
+                snake.turn(*direction);
+            }
+
+            let next = snake.head().step(snake.direction);
+            let new_head = match self.config.wall_mode {
+                WallMode::Clamp => board.clamp(next),
+                WallMode::Wrap => board.wrap(next),
+                WallMode::Solid if snake.invincible => board.clamp(next),
+                WallMode::Solid if !board.contains(next) => {
+                    snake.die(DeathCause::Wall);
+                    events.push(GameEvent::SnakeDied { snake: index, cause: DeathCause::Wall });
+                    continue;
+                }
+                WallMode::Solid => next,
+            };
+            // pushing into a clamped wall just holds the snake where it is
+            if new_head == snake.head() {
+                continue;
+            }
+            snake.body.push_front(new_head);
+            if snake.pending_growth > 0 {
+                snake.pending_growth -= 1;
+                events.push(GameEvent::Grew { snake: index });
+            } else {
+                snake.body.pop_back();
+            }
+
+            let bit_itself = snake.body.iter().skip(1).any(|cell| *cell == new_head);
+            if bit_itself && snake.invincible {
+                snake.body.truncate(1);
+                snake.pending_growth = 0;
+                events.push(GameEvent::TailReset { snake: index });
+            } else if bit_itself {
+                snake.die(DeathCause::OwnBody);
+                events.push(GameEvent::SnakeDied { snake: index, cause: DeathCause::OwnBody });
+            }
+        }
+
+        if let Some(food) = self.food {
+            let eater = self.snakes.iter().position(|snake| snake.is_alive() && snake.head() == food);
+            if let Some(index) = eater {
+                self.snakes[index].pending_growth += self.config.growth;
+                self.score += 1;
+                self.food = None;
+                events.push(GameEvent::FoodEaten { snake: index, cell: food });
+            }
+        }
+
+        if self.food.is_none() {
+            match self.spawn_food() {
+                Some(cell) => events.push(GameEvent::FoodSpawned { cell }),
+                None => events.push(GameEvent::BoardFull),
+            }
+        }
+
+        events
+    }
+
+    // Brings a dead snake back as a single segment, using up one of its lives.
+    // Returns None if it's alive or has no lives left.
+    pub fn respawn(&mut self, snake: usize) -> Option<GameEvent> {
+        let dead = self.snakes.get(snake).filter(|s| !s.is_alive() && s.lives > 0)?;
+        let lives = dead.lives;
+        let invincible = dead.invincible;
+
+        // the old body is taken out first so it doesn't block its own respawn
+        let old_body = std::mem::take(&mut self.snakes[snake].body);
+        let center = self.config.board.center();
+        let head = if self.is_free(center) { Some(center) } else { self.random_free_cell() };
+        match head {
+            Some(head) => {
+                self.snakes[snake] = Snake::new(head, Direction::Up, lives);
+                self.snakes[snake].invincible = invincible;
+                Some(GameEvent::Respawned { snake })
+            }
+            None => {
+                self.snakes[snake].body = old_body;
+                None
+            }
+        }
+    }
+
+    // Add a segment to the end of a snake without eating anything
+    pub fn grow(&mut self, snake: usize) {
+        if let Some(snake) = self.snakes.get_mut(snake) {
+            snake.pending_growth += 1;
+        }
+    }
+
+    // Drop the last segment, a snake never gets shorter than its head.
+    // Returns None if there was nothing to drop.
+    pub fn shrink(&mut self, snake: usize) -> Option<GameEvent> {
+        let body = &mut self.snakes.get_mut(snake).filter(|s| s.body.len() > 1)?.body;
+        body.pop_back();
+        Some(GameEvent::Shrank { snake })
+    }
+
+    pub fn set_invincible(&mut self, snake: usize, invincible: bool) {
+        if let Some(snake) = self.snakes.get_mut(snake) {
+            snake.invincible = invincible;
+        }
+    }
+
+    // Moves the food to a cell of your choosing, as long as the cell is empty
+    pub fn place_food(&mut self, cell: Cell) -> bool {
+        if !self.config.board.contains(cell) || !self.is_free(cell) {
+            return false;
+        }
+        self.food = Some(cell);
+        true
+    }
+
+    fn spawn_food(&mut self) -> Option<Cell> {
+        self.food = self.random_free_cell();
+        self.food
+    }
+
+    // Picks evenly between every cell that has nothing in it, None if there aren't any
+    fn random_free_cell(&mut self) -> Option<Cell> {
+        let board = self.config.board;
+        let mut occupied: HashSet<Cell> = self.obstacles.clone();
+        occupied.extend(self.snakes.iter().flat_map(|snake| snake.body.iter().copied()));
+        occupied.extend(self.food);
+
+        let free: Vec<Cell> = (0..board.height)
+            .flat_map(|y| (0..board.width).map(move |x| Cell::new(x, y)))
+            .filter(|cell| !occupied.contains(cell))
+            .collect();
+        if free.is_empty() {
+            return None;
+        }
+
+        Some(free[self.rng.gen_range(0..free.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(width: i32, height: i32, wall_mode: WallMode) -> GameConfig {
+        GameConfig { board: Board::new(width, height), wall_mode, ..GameConfig::default() }
+    }
+
+    // a game with snake 0 laid out head first along `body` and the food where we want it
+    fn game_with(config: GameConfig, body: &[(i32, i32)], direction: Direction, food: (i32, i32)) -> GameState {
+        let mut game = GameState::new(config, 1);
+        game.snakes[0].body = body.iter().map(|&(x, y)| Cell::new(x, y)).collect();
+        game.snakes[0].direction = direction;
+        game.food = None;
+        assert!(game.place_food(Cell::new(food.0, food.1)));
+        game
+    }
+
+    fn body(game: &GameState) -> Vec<(i32, i32)> {
+        game.snakes[0].body.iter().map(|cell| (cell.x, cell.y)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let config = config(16, 12, WallMode::Solid);
+        let turns = [None, Some(Direction::Left), None, Some(Direction::Down), None, Some(Direction::Right)];
+        let mut first = GameState::new(config, 99);
+        let mut second = GameState::new(config, 99);
+        assert_eq!(first.food(), second.food());
+        for turn in turns {
+            assert_eq!(first.step(&[turn]), second.step(&[turn]));
+        }
+        assert_eq!(body(&first), body(&second));
+        assert_eq!(first.food(), second.food());
+    }
+
+    #[test]
+    fn eating_scores_and_grows_on_the_next_step() {
+        let mut game = game_with(config(9, 9, WallMode::Solid), &[(4, 4)], Direction::Up, (4, 5));
+
+        let events = game.step(&[None]);
+        assert!(events.contains(&GameEvent::FoodEaten { snake: 0, cell: Cell::new(4, 5) }));
+        assert_eq!(game.score(), 1);
+        assert_eq!(game.snakes()[0].pending_growth(), 1);
+        let food = game.food().unwrap();
+        assert!(events.contains(&GameEvent::FoodSpawned { cell: food }));
+        assert!(!game.snakes()[0].body().contains(&food));
+
+        assert!(game.step(&[None]).contains(&GameEvent::Grew { snake: 0 }));
+        assert_eq!(body(&game), [(4, 6), (4, 5)]);
+    }
+
+    #[test]
+    fn growth_adds_one_segment_per_step() {
+        let config = GameConfig { growth: 3, ..config(9, 9, WallMode::Solid) };
+        let mut game = game_with(config, &[(4, 1)], Direction::Up, (4, 2));
+        game.step(&[None]);
+        // keep the next piece out of the way
+        game.food = None;
+        assert!(game.place_food(Cell::new(0, 0)));
+        for _ in 0..4 {
+            game.step(&[None]);
+        }
+        assert_eq!(game.snakes()[0].length(), 4);
+        assert_eq!(game.snakes()[0].pending_growth(), 0);
+    }
+
+    #[test]
+    fn solid_walls_kill() {
+        let mut game = game_with(config(5, 5, WallMode::Solid), &[(2, 4), (2, 3)], Direction::Up, (0, 0));
+        let events = game.step(&[None]);
+        assert!(events.contains(&GameEvent::SnakeDied { snake: 0, cause: DeathCause::Wall }));
+        assert_eq!(game.snakes()[0].death(), Some(DeathCause::Wall));
+        assert_eq!(game.snakes()[0].lives(), 0);
+        assert_eq!(game.respawn(0), None);
+    }
+
+    #[test]
+    fn a_spare_life_respawns_in_the_middle() {
+        let config = GameConfig { lives: 2, ..config(5, 5, WallMode::Solid) };
+        let mut game = game_with(config, &[(0, 2), (1, 2)], Direction::Left, (4, 4));
+        game.step(&[None]);
+        assert_eq!(game.respawn(0), Some(GameEvent::Respawned { snake: 0 }));
+        assert!(game.snakes()[0].is_alive());
+        assert_eq!(game.snakes()[0].lives(), 1);
+        assert_eq!(body(&game), [(2, 2)]);
+    }
+
+    #[test]
+    fn biting_its_own_body_kills() {
+        let coiled = [(2, 2), (2, 1), (3, 1), (3, 2), (3, 3)];
+        let mut game = game_with(config(6, 6, WallMode::Solid), &coiled, Direction::Up, (0, 0));
+        let events = game.step(&[Some(Direction::Right)]);
+        assert!(events.contains(&GameEvent::SnakeDied { snake: 0, cause: DeathCause::OwnBody }));
+        assert_eq!(game.snakes()[0].death(), Some(DeathCause::OwnBody));
+    }
+
+    #[test]
+    fn following_its_own_tail_is_safe() {
+        // the tail moves out of the way in the same step the head moves in
+        let coiled = [(2, 2), (2, 1), (3, 1), (3, 2)];
+        let mut game = game_with(config(6, 6, WallMode::Solid), &coiled, Direction::Up, (0, 0));
+        game.step(&[Some(Direction::Right)]);
+        assert!(game.snakes()[0].is_alive());
+        assert_eq!(body(&game), [(3, 2), (2, 2), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn invincible_snakes_lose_their_tail_instead() {
+        let coiled = [(2, 2), (2, 1), (3, 1), (3, 2), (3, 3)];
+        let mut game = game_with(config(6, 6, WallMode::Solid), &coiled, Direction::Up, (0, 0));
+        game.set_invincible(0, true);
+        let events = game.step(&[Some(Direction::Right)]);
+        assert!(events.contains(&GameEvent::TailReset { snake: 0 }));
+        assert!(game.snakes()[0].is_alive());
+        assert_eq!(body(&game), [(3, 2)]);
+    }
+
+    #[test]
+    fn reversing_is_ignored() {
+        let mut game = game_with(config(9, 9, WallMode::Solid), &[(4, 4), (4, 3)], Direction::Up, (0, 0));
+        game.step(&[Some(Direction::Down)]);
+        assert!(game.snakes()[0].is_alive());
+        assert_eq!(game.snakes()[0].direction(), Direction::Up);
+        assert_eq!(body(&game), [(4, 5), (4, 4)]);
+    }
+
+    #[test]
+    fn wrap_comes_out_the_other_side() {
+        let mut game = game_with(config(5, 5, WallMode::Wrap), &[(2, 4), (2, 3)], Direction::Up, (4, 4));
+        game.step(&[None]);
+        assert_eq!(body(&game), [(2, 0), (2, 4)]);
+        game.step(&[Some(Direction::Left)]);
+        game.step(&[None]);
+        game.step(&[None]);
+        assert_eq!(body(&game), [(4, 0), (0, 0)]);
+        assert!(game.snakes()[0].is_alive());
+    }
+
+    #[test]
+    fn clamp_holds_the_snake_against_the_wall() {
+        let mut game = game_with(config(5, 5, WallMode::Clamp), &[(2, 4), (2, 3), (2, 2)], Direction::Up, (0, 0));
+        game.step(&[None]);
+        game.step(&[None]);
+        assert!(game.snakes()[0].is_alive());
+        assert_eq!(body(&game), [(2, 4), (2, 3), (2, 2)]);
+        // and it can still turn away
+        game.step(&[Some(Direction::Right)]);
+        assert_eq!(body(&game), [(3, 4), (2, 4), (2, 3)]);
+    }
+
+    #[test]
+    fn no_room_for_food_means_the_board_is_full() {
+        let mut game = game_with(config(2, 2, WallMode::Solid), &[(0, 0), (0, 1), (1, 1)], Direction::Down, (1, 0));
+        game.grow(0);
+        let events = game.step(&[Some(Direction::Right)]);
+        assert!(events.contains(&GameEvent::BoardFull));
+        assert_eq!(game.food(), None);
+        assert_eq!(game.snakes()[0].length(), 4);
+    }
+
+    #[test]
+    fn shrinking_stops_at_the_head() {
+        let mut game = game_with(config(9, 9, WallMode::Solid), &[(4, 4), (4, 3)], Direction::Up, (0, 0));
+        assert_eq!(game.shrink(0), Some(GameEvent::Shrank { snake: 0 }));
+        assert_eq!(body(&game), [(4, 4)]);
+        assert_eq!(game.shrink(0), None);
+        assert_eq!(game.shrink(1), None);
+    }
+}
@@ -0,0 +1,304 @@
+use std::path::PathBuf;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use serde::Deserialize;
+
+use snake_game::sim::Direction;
+use snake_game::storage;
+
+use crate::board::{step_between, BoardConfig, GridPos};
+use crate::settings::Settings;
+use crate::snake::{Sim, SnakeSegment};
+use crate::theme::CurrentTheme;
+
+// Loads the snake skins from assets/skins and keeps the handles every segment shares
+pub struct SkinPlugin;
+
+impl Plugin for SkinPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (find_skins, load_snake_look).chain())
+            .add_systems(
+                Update,
+                (
+                    load_snake_look.run_if(resource_changed::<Settings>),
+                    recolor_materials.run_if(resource_changed::<CurrentTheme>),
+                ),
+            )
+            .add_systems(PostUpdate, (orient_segments, paint_segments));
+    }
+}
+
+// the skin name that means plain squares instead of sprites
+pub const FLAT_SKIN: &str = "flat";
+
+// how many shades the flat look's head to tail gradient is split into
+const GRADIENT_STEPS: usize = 16;
+
+// skin.ron in each skin's folder. The sprites are drawn for a snake going up the screen,
+// the corner piece joins the top and right edges and the tail joins the body at the top.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct SkinFile {
+    texture: String,
+    tile_size: u32,
+    columns: usize,
+    rows: usize,
+    head: usize,
+    straight: usize,
+    corner: usize,
+    tail: usize,
+}
+
+// every skin found at startup, sorted by name
+#[derive(Resource, Default)]
+pub struct Skins(Vec<(String, SkinFile)>);
+
+impl Skins {
+    // flat first, then whatever is in the skins folder
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(FLAT_SKIN).chain(self.0.iter().map(|(name, _)| name.as_str()))
+    }
+
+    pub fn next_after(&self, current: &str) -> String {
+        let names: Vec<&str> = self.names().collect();
+        let index = names.iter().position(|name| *name == current).map_or(0, |index| index + 1);
+        names[index % names.len()].to_string()
+    }
+}
+
+struct SkinSprites {
+    texture: Handle<Image>,
+    layout: Handle<TextureAtlasLayout>,
+    head: usize,
+    straight: usize,
+    corner: usize,
+    tail: usize,
+}
+
+// What the snake is drawn with. The flat look shares one mesh and a material per shade of
+// the gradient between every segment, the sprite skin shares one texture and atlas.
+#[derive(Resource)]
+pub struct SnakeLook {
+    skin: String,
+    mesh: Mesh2dHandle,
+    materials: Vec<Handle<ColorMaterial>>,
+    sprites: Option<SkinSprites>,
+}
+
+impl SnakeLook {
+    // the flat look stretches segments to fill in corners, that would just squash a sprite
+    pub fn stretches_corners(&self) -> bool {
+        self.sprites.is_none()
+    }
+
+    pub fn spawn_segment(&self, commands: &mut Commands, board_config: &BoardConfig, bundle: impl Bundle) -> Entity {
+        let transform = Transform::from_xyz(0., 0., 0.5);
+        match &self.sprites {
+            Some(sprites) => commands
+                .spawn((
+                    SpriteSheetBundle {
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::splat(board_config.cell_px)),
+                            ..default()
+                        },
+                        texture: sprites.texture.clone(),
+                        atlas: TextureAtlas {
+                            layout: sprites.layout.clone(),
+                            index: sprites.straight,
+                        },
+                        transform,
+                        ..default()
+                    },
+                    bundle,
+                ))
+                .id(),
+            None => commands
+                .spawn((
+                    MaterialMesh2dBundle {
+                        mesh: self.mesh.clone(),
+                        material: self.materials[0].clone(),
+                        transform,
+                        ..default()
+                    },
+                    bundle,
+                ))
+                .id(),
+        }
+    }
+}
+
+fn skins_dir() -> PathBuf {
+    FileAssetReader::get_base_path().join("assets").join("skins")
+}
+
+fn find_skins(mut commands: Commands) {
+    let mut skins = Vec::new();
+    match std::fs::read_dir(skins_dir()) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = entry.path().join("skin.ron");
+                if !path.exists() {
+                    continue;
+                }
+                match storage::load_ron::<SkinFile>(&path) {
+                    Ok(skin) => skins.push((name, skin)),
+                    Err(err) => warn!("Skipping skin {}: {}", name, err),
+                }
+            }
+        }
+        Err(err) => warn!("No skins loaded from {}: {}", skins_dir().display(), err),
+    }
+    skins.sort_by(|(a, _), (b, _)| a.cmp(b));
+    commands.insert_resource(Skins(skins));
+}
+
+// Picks up the skin named in the settings, segments spawned from then on use it
+fn load_snake_look(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    skins: Res<Skins>,
+    look: Option<Res<SnakeLook>>,
+    asset_server: Res<AssetServer>,
+    board_config: Res<BoardConfig>,
+    theme: Res<CurrentTheme>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    if look.as_ref().is_some_and(|look| look.skin == settings.skin) {
+        return;
+    }
+    let (mesh, shades) = match &look {
+        Some(look) => (look.mesh.clone(), look.materials.clone()),
+        None => (
+            meshes.add(Rectangle::new(board_config.cell_px, board_config.cell_px)).into(),
+            (0..GRADIENT_STEPS).map(|step| materials.add(theme.snake_color(shade(step)))).collect(),
+        ),
+    };
+
+    let skin = skins.0.iter().find(|(name, _)| *name == settings.skin);
+    if skin.is_none() && settings.skin != FLAT_SKIN {
+        warn!("No skin called {}, using flat", settings.skin);
+    }
+    let sprites = skin.map(|(name, skin)| SkinSprites {
+        texture: asset_server.load(format!("skins/{}/{}", name, skin.texture)),
+        layout: layouts.add(TextureAtlasLayout::from_grid(
+            Vec2::splat(skin.tile_size as f32),
+            skin.columns,
+            skin.rows,
+            None,
+            None,
+        )),
+        head: skin.head,
+        straight: skin.straight,
+        corner: skin.corner,
+        tail: skin.tail,
+    });
+    commands.insert_resource(SnakeLook {
+        skin: settings.skin.clone(),
+        mesh,
+        materials: shades,
+        sprites,
+    });
+}
+
+// how far down the gradient a shade of the flat look is
+fn shade(step: usize) -> f32 {
+    step as f32 / (GRADIENT_STEPS - 1) as f32
+}
+
+// the shared materials are changed in place so every segment picks up a new theme
+fn recolor_materials(look: Res<SnakeLook>, theme: Res<CurrentTheme>, mut materials: ResMut<Assets<ColorMaterial>>) {
+    for (step, handle) in look.materials.iter().enumerate() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color = theme.snake_color(shade(step));
+        }
+    }
+}
+
+// Colours each segment by how far down the body it is, head to tail. Sprite skins are drawn
+// in greys so tinting them gives the theme's colours.
+fn paint_segments(
+    sim: Res<Sim>,
+    look: Res<SnakeLook>,
+    theme: Res<CurrentTheme>,
+    mut flat: Query<(&SnakeSegment, &mut Handle<ColorMaterial>)>,
+    mut sprites: Query<(&SnakeSegment, &mut Sprite)>,
+) {
+    let along = |segment: &SnakeSegment| {
+        let length = sim.snakes()[segment.snake].length();
+        segment.segement_index as f32 / length.saturating_sub(1).max(1) as f32
+    };
+    for (segment, mut material) in &mut flat {
+        let step = (along(segment).min(1.) * (GRADIENT_STEPS - 1) as f32).round() as usize;
+        if *material != look.materials[step] {
+            *material = look.materials[step].clone();
+        }
+    }
+    for (segment, mut sprite) in &mut sprites {
+        let color = theme.snake_color(along(segment));
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+// sprites face up, this turns them to face `direction`
+fn facing(direction: Direction) -> Quat {
+    let quarter_turns = match direction {
+        Direction::Up => 0.,
+        Direction::Left => 1.,
+        Direction::Down => 2.,
+        Direction::Right => 3.,
+    };
+    Quat::from_rotation_z(quarter_turns * std::f32::consts::FRAC_PI_2)
+}
+
+fn clockwise(direction: Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Right,
+        Direction::Right => Direction::Down,
+        Direction::Down => Direction::Left,
+        Direction::Left => Direction::Up,
+    }
+}
+
+// Which sprite a segment uses and which way it's turned, worked out from the segments
+// either side of it
+fn orient_segments(
+    sim: Res<Sim>,
+    look: Res<SnakeLook>,
+    mut segments: Query<(&SnakeSegment, &GridPos, &mut TextureAtlas, &mut Transform)>,
+) {
+    let Some(sprites) = &look.sprites else { return };
+    let board = sim.board();
+    for (segment, grid_pos, mut atlas, mut transform) in &mut segments {
+        let snake = &sim.snakes()[segment.snake];
+        let body = snake.body();
+        let index = segment.segement_index as usize;
+        let cell = (*grid_pos).into();
+        // directions from this segment towards the head and towards the tail
+        let ahead = index.checked_sub(1).and_then(|i| body.get(i)).and_then(|c| step_between(board, cell, *c));
+        let behind = body.get(index + 1).and_then(|c| step_between(board, cell, *c));
+
+        let (sprite, rotation) = match (ahead, behind) {
+            _ if index == 0 => (sprites.head, facing(snake.direction())),
+            (Some((ahead, _)), None) => (sprites.tail, facing(ahead)),
+            (Some((ahead, _)), Some((behind, _))) if ahead == behind.opposite() => (sprites.straight, facing(ahead)),
+            // the corner sprite joins up and right, turned so those land on the two neighbours
+            (Some((ahead, _)), Some((behind, _))) => {
+                let turned = if clockwise(ahead) == behind { ahead } else { behind };
+                (sprites.corner, facing(turned))
+            }
+            _ => (sprites.straight, transform.rotation),
+        };
+        if atlas.index != sprite {
+            atlas.index = sprite;
+        }
+        if transform.rotation != rotation {
+            transform.rotation = rotation;
+        }
+    }
+}
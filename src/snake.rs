@@ -0,0 +1,625 @@
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::GlobalEntropy;
+use rand_core::RngCore;
+use std::collections::VecDeque;
+use std::time::*;
+
+use snake_game::ai::{Pilot, SnakeController, Strategy};
+use snake_game::bot::{StdioBot, DEFAULT_BOT_TIMEOUT};
+use snake_game::sim::{Board, Cell, DeathCause, Direction, GameConfig, GameEvent, GameState, SpeedCurve, TurnBuffer};
+
+use crate::board::{step_between, BoardConfig, GridPos, Motion};
+use crate::cli::Args;
+use crate::controls::{Action, PlayerActions};
+use crate::debug::DebugMode;
+use crate::leaderboard::{game_over_state, HighScores};
+use crate::replays::{Playback, Recording};
+use crate::settings::Settings;
+use crate::skin::SnakeLook;
+use crate::theme::CurrentTheme;
+use crate::AppState;
+
+pub struct SnakePlugin;
+
+impl Plugin for SnakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerInput>()
+            .init_resource::<RunClock>()
+            .add_event::<SimEvent>()
+            .add_event::<RunStarted>()
+            .add_event::<SnakeDied>()
+            .add_systems(Startup, setup)
+            .add_systems(OnTransition { from: AppState::MainMenu, to: AppState::Playing }, (start_run, reset_run_stats))
+            .add_systems(OnTransition { from: AppState::GameOver, to: AppState::Playing }, (start_run, reset_run_stats))
+            .add_systems(
+                Update,
+                (
+                    buffer_turns,
+                    tick_run_clock,
+                    play_death_animation.run_if(resource_exists::<DeathAnimation>),
+                    prompt_pilots
+                        .run_if(not(resource_exists::<DeathAnimation>))
+                        .run_if(not(resource_exists::<Playback>)),
+                )
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(Update, (speed_up, report_deaths))
+            .add_systems(
+                FixedUpdate,
+                (
+                    move_snake.run_if(not(resource_exists::<DeathAnimation>)),
+                    sync_snake_segments,
+                    sync_food,
+                ).chain().run_if(in_state(AppState::Playing).or_else(resource_exists::<StepOnce>)),
+            );
+    }
+}
+
+// The actual game, every rule lives in snake_game::sim and the systems here only mirror it
+#[derive(Resource, Deref, DerefMut)]
+pub struct Sim(GameState);
+
+// Ask for a single step right away, even while paused. Only the debug mode uses this.
+#[derive(Resource)]
+pub struct StepOnce;
+
+// Bevy side copy of everything the sim reported during a step
+#[derive(Event)]
+pub struct SimEvent(pub GameEvent);
+
+// sent once the board has been reset for a new run
+#[derive(Event)]
+pub struct RunStarted;
+
+#[derive(Event)]
+pub struct SnakeDied {
+    pub snake: usize,
+    pub cause: DeathCause,
+    pub length: usize,
+    pub score: u32,
+}
+
+// The game holds still and the dead snake blinks for a moment before it respawns or
+// the run ends
+#[derive(Resource)]
+pub struct DeathAnimation {
+    snake: usize,
+    timer: Timer,
+}
+
+impl DeathAnimation {
+    pub fn start(snake: usize) -> Self {
+        DeathAnimation {
+            snake,
+            timer: Timer::new(DEATH_ANIMATION_TIME, TimerMode::Once),
+        }
+    }
+}
+
+const DEATH_ANIMATION_TIME: Duration = Duration::from_millis(1200);
+const DEATH_BLINK_TIME: Duration = Duration::from_millis(150);
+
+// how long the current run has been played for, doesn't count time spent paused
+#[derive(Resource, Default)]
+pub struct RunClock(pub Stopwatch);
+
+// Queued turns for each player and the AI steering any snake a player isn't, indexed the
+// same as the sim's snakes
+#[derive(Resource, Default)]
+pub struct PlayerInput {
+    turns: Vec<TurnBuffer>,
+    ai: Vec<Option<Box<dyn SnakeController + Send + Sync>>>,
+    // the debug autopilot flying the first snake, kept on from one run to the next
+    autopilot: Option<Strategy>,
+    // a program steering the first snake for --bot-stdio
+    bot: Option<StdioBot>,
+    // steps that are due while one of the AI is still thinking, they're all taken once it's done
+    owed: u32,
+}
+
+impl PlayerInput {
+    pub fn turns(&self, player: usize) -> Option<&TurnBuffer> {
+        self.turns.get(player)
+    }
+
+    fn reset(&mut self, players: usize, depth: usize, pilot: Pilot) {
+        self.turns = vec![TurnBuffer::new(depth); players];
+        self.ai = (0..players).map(|player| self.controller(player, pilot)).collect();
+        self.owed = 0;
+    }
+
+    // the autopilot takes over from a bot, which takes over from the settings. Only the first
+    // snake has a pilot, anyone else is a player.
+    fn controller(&self, player: usize, pilot: Pilot) -> Option<Box<dyn SnakeController + Send + Sync>> {
+        if player > 0 {
+            return None;
+        }
+        match (self.autopilot, &self.bot) {
+            (Some(strategy), _) => Some(strategy.controller()),
+            (None, Some(bot)) => Some(Box::new(bot.clone())),
+            (None, None) => pilot.controller(),
+        }
+    }
+
+    pub fn is_ai(&self, player: usize) -> bool {
+        matches!(self.ai.get(player), Some(Some(_)))
+    }
+
+    // AI runs don't go on the high score table or leave a ghost behind
+    pub fn ai_playing(&self) -> bool {
+        self.ai.iter().any(Option::is_some)
+    }
+
+    pub fn autopilot(&self) -> Option<Strategy> {
+        self.autopilot
+    }
+
+    // Hands the first snake to the AI, or back to whoever the settings say flies it
+    pub fn set_autopilot(&mut self, autopilot: Option<Strategy>, pilot: Pilot) {
+        self.autopilot = autopilot;
+        let controller = self.controller(0, pilot);
+        if let Some(ai) = self.ai.get_mut(0) {
+            *ai = controller;
+        }
+        if let Some(turns) = self.turns.get_mut(0) {
+            turns.clear();
+        }
+    }
+
+    pub fn pilot_name(&self, player: usize) -> &'static str {
+        match self.ai.get(player) {
+            Some(Some(ai)) => ai.name(),
+            _ => Pilot::Human.name(),
+        }
+    }
+
+    // asks every AI about the next step, true once they've all made up their minds
+    fn ready(&mut self, game: &GameState) -> bool {
+        let mut ready = true;
+        for (snake, ai) in self.ai.iter_mut().enumerate() {
+            if let Some(ai) = ai {
+                ready &= ai.ready(game, snake);
+            }
+        }
+        ready
+    }
+
+    fn next_turn(&mut self, game: &GameState, snake: usize) -> Option<Direction> {
+        match (self.ai.get_mut(snake), self.turns.get_mut(snake)) {
+            (Some(Some(ai)), _) => ai.next_turn(game, snake),
+            (_, Some(turns)) => turns.next_turn(game, snake),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SnakeHead {
+    pub segement_count: u32,
+}
+
+#[derive(Component)]
+pub struct SnakeSegment {
+    pub snake: usize,
+    pub segement_index: u32,
+}
+
+#[derive(Resource)]
+pub struct MoveTime {
+    // track when the snake should update positions
+    pub timer: Timer,
+}
+
+// how the move time shrinks as the score goes up, picked from the settings at the start of a run
+#[derive(Resource, Deref)]
+pub struct SpeedProgression(SpeedCurve);
+
+#[derive(Component)]
+pub struct Food;
+
+fn setup (
+    mut commands: Commands,
+    board_config: Res<BoardConfig>,
+    settings: Res<Settings>,
+    args: Res<Args>,
+    debug: Res<DebugMode>,
+    mut entropy: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    mut player_input: ResMut<PlayerInput>,
+) {
+    let curve = settings.speed_curve();
+    if args.bot_stdio {
+        let timeout = args.bot_timeout.map_or(DEFAULT_BOT_TIMEOUT, Duration::from_millis);
+        player_input.bot = Some(StdioBot::spawn(timeout));
+    }
+    commands.insert_resource(MoveTime { timer: Timer::new(curve.interval(0), TimerMode::Repeating) });
+    commands.insert_resource(SpeedProgression(curve));
+    commands.insert_resource(Sim(new_game(&board_config, &settings, &args, &debug, &mut entropy)));
+}
+
+// Every run gets its own seed so it can be played again with --seed. Passing --seed, or
+// setting seed in config.ron, pins every run to that seed, otherwise a new one is drawn
+// from the global rng.
+fn new_game(
+    board_config: &BoardConfig,
+    settings: &Settings,
+    args: &Args,
+    debug: &DebugMode,
+    entropy: &mut GlobalEntropy<ChaCha8Rng>,
+) -> GameState {
+    let config = GameConfig {
+        board: board_config.board(),
+        wall_mode: settings.wall_mode,
+        lives: settings.lives,
+        growth: settings.growth,
+    };
+    let seed = args.seed.or(settings.seed).unwrap_or_else(|| entropy.next_u64());
+    let mut game = GameState::new(config, seed);
+    // debug runs start out with the old sandbox rules
+    game.set_invincible(0, debug.enabled);
+    game
+}
+
+// throw away whatever is left of the last run, the sync systems spawn the new snake
+fn start_run(
+    mut commands: Commands,
+    board_config: Res<BoardConfig>,
+    settings: Res<Settings>,
+    args: Res<Args>,
+    debug: Res<DebugMode>,
+    mut entropy: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    mut sim: ResMut<Sim>,
+    mut player_input: ResMut<PlayerInput>,
+    mut move_time: ResMut<MoveTime>,
+    segments: Query<Entity, With<SnakeSegment>>,
+    food: Query<Entity, With<Food>>,
+    mut run_started: EventWriter<RunStarted>,
+) {
+    for entity in segments.iter().chain(food.iter()) {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<DeathAnimation>();
+    **sim = new_game(&board_config, &settings, &args, &debug, &mut entropy);
+    // the autopilot is a debug tool, it doesn't carry over into a normal run
+    if !debug.enabled {
+        player_input.autopilot = None;
+    }
+    player_input.reset(sim.snakes().len(), settings.turn_buffer, settings.pilot);
+    let curve = settings.speed_curve();
+    move_time.timer.set_duration(curve.interval(0));
+    move_time.timer.reset();
+    commands.insert_resource(SpeedProgression(curve));
+    run_started.send(RunStarted);
+}
+
+fn reset_run_stats(mut run_clock: ResMut<RunClock>) {
+    run_clock.0.reset();
+}
+
+fn tick_run_clock(time: Res<Time>, mut run_clock: ResMut<RunClock>) {
+    run_clock.0.tick(time.delta());
+}
+
+// Only the duration changes, whatever progress the timer has made towards the next
+// move is kept
+fn speed_up(sim: Res<Sim>, speed: Res<SpeedProgression>, mut move_time: ResMut<MoveTime>) {
+    if !sim.is_changed() && !speed.is_changed() {
+        return;
+    }
+    let interval = speed.interval(sim.score());
+    if move_time.timer.duration() != interval {
+        move_time.timer.set_duration(interval);
+    }
+}
+
+fn report_deaths(mut snake_died: EventReader<SnakeDied>) {
+    for death in snake_died.read() {
+        eprintln!(
+            "Snake {}: {}! (length {}, score {})",
+            death.snake,
+            death.cause.describe(),
+            death.length,
+            death.score
+        );
+    }
+}
+
+fn spawn_snake_head(
+    commands: &mut Commands,
+    look: &SnakeLook,
+    board_config: &BoardConfig,
+    grid_pos: GridPos,
+    motion: Motion,
+    snake: usize,
+)
+{
+    look.spawn_segment(
+        commands,
+        board_config,
+        (
+            grid_pos,
+            motion,
+            SnakeHead {
+                segement_count: 0,
+            },
+            SnakeSegment {
+                snake,
+                segement_index: 0,
+            },
+        ),
+    );
+}
+
+fn spawn_snake_segment(
+    commands: &mut Commands,
+    look: &SnakeLook,
+    board_config: &BoardConfig,
+    grid_pos: GridPos,
+    motion: Motion,
+    snake: usize,
+    new_index: u32,
+)
+{
+    look.spawn_segment(
+        commands,
+        board_config,
+        (
+            grid_pos,
+            motion,
+            SnakeSegment {
+                snake,
+                segement_index: new_index
+            },
+        ),
+    );
+}
+
+// Read presses every frame rather than every step so quick turns between two steps
+// aren't lost, they get queued and used up one per step
+fn buffer_turns(
+    player_actions: Res<PlayerActions>,
+    sim: Res<Sim>,
+    mut player_input: ResMut<PlayerInput>,
+) {
+    for player in 0..player_input.turns.len() {
+        // presses would pile up behind the AI and fire all at once when it hands back
+        if player_input.is_ai(player) {
+            continue;
+        }
+        let Some(actions) = player_actions.player(player) else { continue };
+        let moved = sim.snakes()[player].direction();
+        let turns = &mut player_input.turns[player];
+        for (action, direction) in [
+            (Action::Up, Direction::Up),
+            (Action::Down, Direction::Down),
+            (Action::Left, Direction::Left),
+            (Action::Right, Direction::Right),
+        ] {
+            if actions.just_pressed(action) {
+                turns.push(direction, moved);
+            }
+        }
+    }
+}
+
+// Shows the AI the board as soon as it's settled after a step, so a bot has the whole move
+// time to think rather than just its timeout
+fn prompt_pilots(sim: Res<Sim>, mut player_input: ResMut<PlayerInput>) {
+    player_input.ready(&sim);
+}
+
+pub fn move_snake(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut sim: ResMut<Sim>,
+    mut player_input: ResMut<PlayerInput>,
+    mut move_time: ResMut<MoveTime>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut sim_events: EventWriter<SimEvent>,
+    mut snake_died: EventWriter<SnakeDied>,
+    high_scores: Res<HighScores>,
+    debug: Res<DebugMode>,
+    step_once: Option<Res<StepOnce>>,
+    playback: Option<Res<Playback>>,
+    mut recording: Option<ResMut<Recording>>,
+){
+    // the run ended earlier this frame, the state only changes once the fixed steps are done
+    if next_state.0.is_some() {
+        return;
+    }
+    // a fast replay can owe more than one move by the time a fixed step comes round
+    let steps = if step_once.is_some() {
+        1
+    } else if player_input.owed > 0 {
+        player_input.owed
+    } else {
+        let speed = playback.as_ref().map_or(1., |playback| playback.speed);
+        move_time.timer.tick(time.delta().mul_f32(speed));
+        move_time.timer.times_finished_this_tick()
+    };
+    for taken in 0..steps {
+        // A bot gets until its timeout to answer. The steps wait for it a frame at a time
+        // rather than holding the frame up.
+        if playback.is_none() && !player_input.ready(&sim) {
+            player_input.owed = steps - taken;
+            return;
+        }
+        player_input.owed = 0;
+        if step_once.is_some() {
+            commands.remove_resource::<StepOnce>();
+        }
+
+        // a replay's turns are the ones the players or the AI made when it was recorded
+        let inputs: Vec<Option<Direction>> = match &playback {
+            Some(playback) => playback.replay.inputs_at(sim.tick(), sim.snakes().len()),
+            None => (0..sim.snakes().len()).map(|snake| player_input.next_turn(&sim, snake)).collect(),
+        };
+        if let Some(recording) = &mut recording {
+            recording.0.record(sim.tick(), &inputs);
+        }
+        let mut died = false;
+        let events = sim.step(&inputs);
+        for event in events {
+            match event {
+                GameEvent::FoodSpawned { cell } => debug!("Food spawned at {}, {}", cell.x, cell.y),
+                GameEvent::SnakeDied { snake, cause } => {
+                    snake_died.send(SnakeDied {
+                        snake,
+                        cause,
+                        length: sim.snakes()[snake].length(),
+                        score: sim.score(),
+                    });
+                    commands.insert_resource(DeathAnimation::start(snake));
+                    died = true;
+                }
+                GameEvent::BoardFull => {
+                    eprintln!("No room left for food, you win!");
+                    let unranked = playback.is_some() || player_input.ai_playing();
+                    next_state.set(game_over_state(&high_scores, &debug, unranked, sim.score()));
+                }
+                _ => {}
+            }
+            sim_events.send(SimEvent(event));
+        }
+        // the rest waits until the death has played out
+        if died || next_state.0.is_some() {
+            return;
+        }
+    }
+}
+
+fn play_death_animation(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut death: ResMut<DeathAnimation>,
+    mut sim: ResMut<Sim>,
+    mut player_input: ResMut<PlayerInput>,
+    mut segments: Query<(&SnakeSegment, &mut Visibility)>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut sim_events: EventWriter<SimEvent>,
+    high_scores: Res<HighScores>,
+    debug: Res<DebugMode>,
+    playback: Option<Res<Playback>>,
+) {
+    death.timer.tick(time.delta());
+    let blinks = death.timer.elapsed().as_millis() / DEATH_BLINK_TIME.as_millis();
+    let visibility = if death.timer.finished() || blinks % 2 == 1 { Visibility::Inherited } else { Visibility::Hidden };
+    for (segment, mut segment_visibility) in &mut segments {
+        if segment.snake == death.snake {
+            *segment_visibility = visibility;
+        }
+    }
+
+    if !death.timer.finished() {
+        return;
+    }
+    commands.remove_resource::<DeathAnimation>();
+    match sim.respawn(death.snake) {
+        Some(event) => {
+            if let Some(turns) = player_input.turns.get_mut(death.snake) {
+                turns.clear();
+            }
+            sim_events.send(SimEvent(event));
+        }
+        None => {
+            let unranked = playback.is_some() || player_input.ai_playing();
+            next_state.set(game_over_state(&high_scores, &debug, unranked, sim.score()));
+        }
+    }
+}
+
+// make the snake entities match the sim, one entity per body cell
+fn sync_snake_segments(
+    mut commands: Commands,
+    look: Res<SnakeLook>,
+    board_config: Res<BoardConfig>,
+    sim: Res<Sim>,
+    mut snake_query: Query<(Entity, &SnakeSegment, &mut GridPos, &mut Motion)>,
+    mut snake_head_query: Query<(&SnakeSegment, &mut SnakeHead)>,
+) {
+    let mut segment_counts = vec![0; sim.snakes().len()];
+    let board = sim.board();
+
+    for (entity, segment, mut grid_pos, mut motion) in &mut snake_query {
+        let body = sim.snakes()[segment.snake].body();
+        let index = segment.segement_index as usize;
+        match body.get(index) {
+            Some(cell) => {
+                // each segment slides out of the cell it was in before this tick's move
+                if motion.tick != sim.tick() {
+                    *motion = Motion {
+                        from: *grid_pos,
+                        tick: sim.tick(),
+                        corner: look.stretches_corners() && is_corner(board, body, index),
+                    };
+                }
+                grid_pos.set_if_neq((*cell).into());
+                segment_counts[segment.snake] += 1;
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for (index, snake) in sim.snakes().iter().enumerate() {
+        for segment_index in segment_counts[index]..snake.length() {
+            let grid_pos = GridPos::from(snake.body()[segment_index]);
+            let motion = Motion::at(grid_pos, sim.tick());
+            if segment_index == 0 {
+                spawn_snake_head(&mut commands, &look, &board_config, grid_pos, motion, index);
+                continue;
+            }
+            spawn_snake_segment(&mut commands, &look, &board_config, grid_pos, motion, index, segment_index as u32);
+            debug!("New snake segment spawned: index {}, position {:?}", segment_index, grid_pos);
+        }
+    }
+
+    for (segment, mut snake_head) in &mut snake_head_query {
+        snake_head.segement_count = sim.snakes()[segment.snake].length() as u32 - 1;
+    }
+}
+
+// The segment at `index` just came out of a turn if the one behind it moved a different way
+fn is_corner(board: Board, body: &VecDeque<Cell>, index: usize) -> bool {
+    let (Some(behind), Some(from), Some(to)) = (body.get(index + 2), body.get(index + 1), body.get(index)) else {
+        return false;
+    };
+    match (step_between(board, *behind, *from), step_between(board, *from, *to)) {
+        (Some((before, _)), Some((after, _))) => before != after,
+        _ => false,
+    }
+}
+
+fn sync_food(
+    mut commands: Commands,
+    board_config: Res<BoardConfig>,
+    sim: Res<Sim>,
+    theme: Res<CurrentTheme>,
+    mut food_query: Query<(Entity, &mut GridPos, &mut Sprite), With<Food>>,
+){
+    let color = theme.food_color(sim.score());
+    match (sim.food(), food_query.get_single_mut()) {
+        (Some(cell), Ok((_, mut grid_pos, mut sprite))) => {
+            grid_pos.set_if_neq(cell.into());
+            if sprite.color != color {
+                sprite.color = color;
+            }
+        }
+        (Some(cell), Err(_)) => {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::new(board_config.cell_px, board_config.cell_px)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                GridPos::from(cell),
+                Food,
+            ));
+        }
+        (None, Ok((food_entity, _, _))) => commands.entity(food_entity).despawn(),
+        (None, Err(_)) => {}
+    }
+}
@@ -0,0 +1,61 @@
+// Reading and writing the RON files the game keeps in the user's directories
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Io(path, err) => write!(f, "could not access {}: {}", path.display(), err),
+            StorageError::Parse(path, err) => write!(f, "{} could not be read: {}", path.display(), err),
+            StorageError::Serialize(err) => write!(f, "could not be written: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+// A missing file gives the default value, anything else that goes wrong is an error
+pub fn load_ron<T: DeserializeOwned + Default>(path: &Path) -> Result<T, StorageError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => return Err(StorageError::Io(path.to_path_buf(), err)),
+    };
+    ron::from_str(&contents).map_err(|err| StorageError::Parse(path.to_path_buf(), err))
+}
+
+// for files that have to be there, like one asked for on the command line
+pub fn load_ron_required<T: DeserializeOwned>(path: &Path) -> Result<T, StorageError> {
+    let contents = fs::read_to_string(path).map_err(|err| StorageError::Io(path.to_path_buf(), err))?;
+    ron::from_str(&contents).map_err(|err| StorageError::Parse(path.to_path_buf(), err))
+}
+
+pub fn save_ron<T: Serialize>(value: &T, path: &Path) -> Result<(), StorageError> {
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(StorageError::Serialize)?;
+    write(path, contents)
+}
+
+// all on one line, for files nobody is meant to edit by hand
+pub fn save_ron_compact<T: Serialize>(value: &T, path: &Path) -> Result<(), StorageError> {
+    write(path, ron::to_string(value).map_err(StorageError::Serialize)?)
+}
+
+fn write(path: &Path, contents: String) -> Result<(), StorageError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| StorageError::Io(dir.to_path_buf(), err))?;
+    }
+    fs::write(path, contents).map_err(|err| StorageError::Io(path.to_path_buf(), err))
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadedFolder};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Deserializer};
+
+use crate::settings::Settings;
+use crate::view::Backdrop;
+
+// Colours for everything on screen, loaded from the .theme.ron files in assets/themes
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Theme>()
+            .init_asset_loader::<ThemeLoader>()
+            .init_resource::<CurrentTheme>()
+            .add_systems(Startup, load_themes)
+            .add_systems(
+                Update,
+                (pick_theme, (apply_background, recolor_text)).chain(),
+            );
+    }
+}
+
+#[derive(Asset, TypePath, Debug, Clone, PartialEq, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    #[serde(deserialize_with = "hex")]
+    pub background: Color,
+    #[serde(deserialize_with = "hex")]
+    pub grid: Color,
+    #[serde(deserialize_with = "hex")]
+    pub snake_head: Color,
+    #[serde(deserialize_with = "hex")]
+    pub snake_tail: Color,
+    #[serde(deserialize_with = "hex_list")]
+    pub food: Vec<Color>,
+    #[serde(deserialize_with = "hex")]
+    pub text: Color,
+    #[serde(deserialize_with = "hex")]
+    pub accent: Color,
+    // menu buttons, and the same buttons under the mouse
+    #[serde(deserialize_with = "hex")]
+    pub button: Color,
+    #[serde(deserialize_with = "hex")]
+    pub button_hover: Color,
+}
+
+// the classic look, used until the theme files have loaded or if they can't be found
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "Classic".to_string(),
+            background: Color::rgb(0.4, 0.4, 0.4),
+            grid: Color::rgba(1., 1., 1., 0.08),
+            snake_head: Color::GREEN,
+            snake_tail: Color::rgb(0., 0.6, 0.),
+            food: vec![Color::rgb(0.8, 0.1, 0.0)],
+            text: Color::WHITE,
+            accent: Color::rgb(0.1, 0.5, 0.1),
+            button: Color::rgb(0.15, 0.15, 0.15),
+            button_hover: Color::rgb(0.25, 0.25, 0.25),
+        }
+    }
+}
+
+impl Theme {
+    // t goes from 0 at the head to 1 at the end of the tail
+    pub fn snake_color(&self, t: f32) -> Color {
+        let [r0, g0, b0, a0] = self.snake_head.as_rgba_f32();
+        let [r1, g1, b1, a1] = self.snake_tail.as_rgba_f32();
+        let t = t.clamp(0., 1.);
+        Color::rgba(r0 + (r1 - r0) * t, g0 + (g1 - g0) * t, b0 + (b1 - b0) * t, a0 + (a1 - a0) * t)
+    }
+
+    // each new piece of food takes the next colour
+    pub fn food_color(&self, eaten: u32) -> Color {
+        match self.food.len() {
+            0 => Theme::default().food[0],
+            len => self.food[eaten as usize % len],
+        }
+    }
+}
+
+fn hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Color::hex(&text).map_err(|err| serde::de::Error::custom(format!("{}: {:?}", text, err)))
+}
+
+fn hex_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|text| Color::hex(text).map_err(|err| serde::de::Error::custom(format!("{}: {:?}", text, err))))
+        .collect()
+}
+
+#[derive(Default)]
+struct ThemeLoader;
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeError::Io(err) => write!(f, "couldn't read theme: {}", err),
+            ThemeError::Parse(err) => write!(f, "bad theme file: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+impl AssetLoader for ThemeLoader {
+    type Asset = Theme;
+    type Settings = ();
+    type Error = ThemeError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Theme, ThemeError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await.map_err(ThemeError::Io)?;
+            ron::de::from_bytes(&bytes).map_err(ThemeError::Parse)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme.ron"]
+    }
+}
+
+// keeps every theme in assets/themes loaded so they can be switched between
+#[derive(Resource)]
+struct ThemeFolder(#[allow(dead_code)] Handle<LoadedFolder>);
+
+// The theme in use, a copy of the asset so it can be read without going through Assets
+#[derive(Resource, Default, Deref, PartialEq)]
+pub struct CurrentTheme(Theme);
+
+// Which part of the theme a bit of text is drawn in
+#[derive(Component, Clone, Copy)]
+pub enum ThemeColor {
+    Text,
+    Accent,
+}
+
+fn load_themes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ThemeFolder(asset_server.load_folder("themes")));
+}
+
+// every theme that has loaded, by name
+pub fn theme_names(themes: &Assets<Theme>) -> Vec<String> {
+    let mut names: Vec<String> = themes.iter().map(|(_, theme)| theme.name.clone()).collect();
+    if names.is_empty() {
+        names.push(Theme::default().name);
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn pick_theme(
+    settings: Res<Settings>,
+    mut theme_events: EventReader<AssetEvent<Theme>>,
+    themes: Res<Assets<Theme>>,
+    mut current: ResMut<CurrentTheme>,
+) {
+    if theme_events.read().count() == 0 && !settings.is_changed() {
+        return;
+    }
+    if let Some((_, theme)) = themes.iter().find(|(_, theme)| theme.name == settings.theme) {
+        current.set_if_neq(CurrentTheme(theme.clone()));
+    }
+}
+
+fn apply_background(theme: Res<CurrentTheme>, mut backdrop: Query<(Ref<Backdrop>, &mut Sprite)>) {
+    for (backdrop, mut sprite) in &mut backdrop {
+        if theme.is_changed() || backdrop.is_added() {
+            sprite.color = theme.background;
+        }
+    }
+}
+
+fn recolor_text(theme: Res<CurrentTheme>, mut texts: Query<(Ref<ThemeColor>, &mut Text)>) {
+    for (color, mut text) in &mut texts {
+        if !theme.is_changed() && !color.is_added() {
+            continue;
+        }
+        let color = match *color {
+            ThemeColor::Text => theme.text,
+            ThemeColor::Accent => theme.accent,
+        };
+        for section in &mut text.sections {
+            section.style.color = color;
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode};
+
+use crate::board::BoardConfig;
+use crate::controls::Action;
+use crate::settings::{change_settings, Settings, SettingsFile};
+
+// Fits the board to the window. The board keeps its size in cells and pixels whatever the
+// window does, the camera zooms it by a whole number so the pixels stay sharp and the rest
+// of the window is left as bars around it.
+pub struct ViewPlugin;
+
+impl Plugin for ViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClearColor(LETTERBOX))
+            .add_systems(Startup, (spawn_camera, spawn_backdrop, apply_fullscreen))
+            .add_systems(
+                Update,
+                (
+                    fit_board,
+                    resize_backdrop.run_if(resource_changed::<BoardConfig>),
+                    toggle_fullscreen,
+                    apply_fullscreen.run_if(resource_changed::<Settings>),
+                ),
+            );
+    }
+}
+
+// colour of the bars either side of the board
+const LETTERBOX: Color = Color::BLACK;
+
+// The board's background, the theme colours it
+#[derive(Component)]
+pub struct Backdrop;
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn spawn_backdrop(mut commands: Commands, board_config: Res<BoardConfig>) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(board_config.width_px(), board_config.height_px())),
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., -1.),
+            ..default()
+        },
+        Backdrop,
+    ));
+}
+
+fn resize_backdrop(board_config: Res<BoardConfig>, mut backdrop: Query<&mut Sprite, With<Backdrop>>) {
+    for mut sprite in &mut backdrop {
+        sprite.custom_size = Some(Vec2::new(board_config.width_px(), board_config.height_px()));
+    }
+}
+
+// How many screen pixels each board pixel takes. Whole numbers when the board fits, a
+// window smaller than the board shrinks it to fit instead of cutting it off.
+fn zoom(board: Vec2, screen: Vec2) -> f32 {
+    let fit = (screen.x / board.x).min(screen.y / board.y);
+    if fit >= 1. {
+        fit.floor()
+    } else {
+        fit.max(f32::EPSILON)
+    }
+}
+
+fn fit_board(
+    board_config: Res<BoardConfig>,
+    window: Query<Ref<Window>, With<PrimaryWindow>>,
+    mut projection: Query<&mut OrthographicProjection>,
+) {
+    let Ok(window) = window.get_single() else { return };
+    if !window.is_changed() && !board_config.is_changed() {
+        return;
+    }
+    let board = Vec2::new(board_config.width_px(), board_config.height_px());
+    let screen = Vec2::new(window.physical_width() as f32, window.physical_height() as f32);
+    if screen.x < 1. || screen.y < 1. {
+        return;
+    }
+    // the projection works in logical pixels, so undo the scale factor to zoom by physical ones
+    let scale = window.scale_factor() / zoom(board, screen);
+    for mut projection in &mut projection {
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+    }
+}
+
+fn toggle_fullscreen(
+    actions: Res<ButtonInput<Action>>,
+    mut settings: ResMut<Settings>,
+    mut settings_file: ResMut<SettingsFile>,
+) {
+    if actions.just_pressed(Action::Fullscreen) {
+        let fullscreen = !settings.fullscreen;
+        change_settings(&mut settings, &mut settings_file, |settings| settings.fullscreen = fullscreen);
+    }
+}
+
+fn apply_fullscreen(settings: Res<Settings>, mut window: Query<&mut Window, With<PrimaryWindow>>) {
+    let Ok(mut window) = window.get_single_mut() else { return };
+    let mode = if settings.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed };
+    if window.mode != mode {
+        window.mode = mode;
+    }
+}